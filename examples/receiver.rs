@@ -1,6 +1,6 @@
-use sixtop_rs::msg_builder::serialize_response;
+use sixtop_rs::msg_builder::serialize_message;
 use sixtop_rs::msg_reader::deserialize_message;
-use sixtop_rs::types::{NeighborID, SixtopMsg};
+use sixtop_rs::types::NeighborID;
 use sixtop_rs::Sixtop;
 use std::io;
 use std::io::{BufRead, BufReader, Write};
@@ -8,7 +8,7 @@ use std::net::{TcpListener, TcpStream};
 
 const IP_AND_PORT: &str = "127.0.0.1:8080";
 
-const DUMMY_SENDER_ADDR: NeighborID = 77;
+const DUMMY_SENDER_ADDR: NeighborID = NeighborID::Short(77);
 
 fn handle_client_connection(
     mut stream: TcpStream,
@@ -23,18 +23,29 @@ fn handle_client_connection(
 
     // todo loop here
     let buffer = stream_reader.fill_buf()?.to_vec();
-    let msg = deserialize_message(buffer).expect("unable to parse message");
+    let msg = match deserialize_message(buffer) {
+        Ok(msg) => msg,
+        Err(error) => {
+            println!("dropping malformed message: {}", error);
+            return Ok(());
+        }
+    };
     println!("received: {:#?}", msg);
 
-    let result = sixtop.handle_msg(DUMMY_SENDER_ADDR, msg).unwrap();
-    if let Some(response) = result {
-        match response {
-            SixtopMsg::ResponseMsg(response) => {
-                let data = serialize_response(response).unwrap();
-                stream.write(data.as_slice())?;
-            }
-            _ => unimplemented!(),
+    let result = match sixtop.handle_msg(DUMMY_SENDER_ADDR, msg) {
+        Ok(result) => result,
+        Err(error) => {
+            println!("unable to handle message: {}", error);
+            return Ok(());
         }
+    };
+    if let Some(response) = result {
+        let seqnum = response.header().seqnum;
+        let data = serialize_message(response).unwrap();
+        stream.write_all(data.as_slice())?;
+        // TCP delivered the response, which is as good as a link-layer ack. Not every response
+        // is part of a transaction that waits for one, so errors are expected here.
+        sixtop.on_link_ack(DUMMY_SENDER_ADDR, seqnum).ok();
     }
     Ok(())
 }
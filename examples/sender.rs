@@ -5,38 +5,14 @@ use std::io::BufReader;
 use std::io::Write;
 use std::net::TcpStream;
 
-use sixtop_rs::msg_builder::serialize_request;
+use sixtop_rs::msg_builder::MAX_MSG_SZ_BYTES;
 use sixtop_rs::msg_reader::deserialize_message;
-use sixtop_rs::types::{Cell, Msg, Request, RequestType};
+use sixtop_rs::types::{Cell, CellOptions, NeighborID};
 use sixtop_rs::Sixtop;
 
 const SERVER_ADDR: &str = "127.0.0.1:8080";
 
-fn build_msg() -> Vec<u8> {
-    let mut test_msg = Request::new();
-    test_msg.header.code = RequestType::ADD as u8;
-    test_msg.header.seqnum = 22;
-
-    test_msg.metadata = 0b1111_1111_0000_0000;
-    test_msg.cell_options = 0b100;
-    test_msg.num_cells = 2;
-    test_msg.cell_list.push(Cell {
-        slot_offset: 1,
-        channel_offset: 2,
-    });
-    test_msg.cell_list.push(Cell {
-        slot_offset: 3,
-        channel_offset: 9,
-    });
-    test_msg.cell_list.push(Cell {
-        slot_offset: 5,
-        channel_offset: 2,
-    });
-
-    println!("Built msg: {:#?}", test_msg);
-    let data = serialize_request(test_msg).unwrap();
-    return data;
-}
+const DUMMY_RECEIVER_ADDR: NeighborID = NeighborID::Short(43);
 
 fn main() {
     let mut stream = TcpStream::connect(SERVER_ADDR).unwrap();
@@ -44,9 +20,38 @@ fn main() {
     let mut sixtop = Sixtop::new();
 
     // send dummy request
-    let request = build_msg();
-    match stream.write(request.as_slice()) {
-        Ok(numbytes) => println!("wrote {} bytes", numbytes),
+    let candidates = [
+        Cell {
+            slot_offset: 1,
+            channel_offset: 2,
+        },
+        Cell {
+            slot_offset: 3,
+            channel_offset: 9,
+        },
+        Cell {
+            slot_offset: 5,
+            channel_offset: 2,
+        },
+    ];
+    sixtop.set_metadata(0b1111_1111_0000_0000);
+    let mut buf = [0; MAX_MSG_SZ_BYTES];
+    let len = sixtop
+        .add_cells(
+            DUMMY_RECEIVER_ADDR,
+            CellOptions::SHARED,
+            2,
+            &candidates,
+            &mut buf,
+        )
+        .unwrap();
+    let seqnum = sixtop.transaction_seqnum(DUMMY_RECEIVER_ADDR).unwrap();
+    match stream.write_all(&buf[..len]) {
+        Ok(()) => {
+            println!("wrote {} bytes", len);
+            // TCP delivered the request, which is as good as a link-layer ack
+            sixtop.on_link_ack(DUMMY_RECEIVER_ADDR, seqnum).unwrap();
+        }
         Err(error) => println!("whoops. err {:?}", error),
     }
     stream.flush().unwrap();
@@ -56,5 +61,5 @@ fn main() {
     let response = deserialize_message(buffer).expect("unable to parse message");
     println!("received: {:#?}", response);
 
-    let result = sixtop.handle_msg(43, response).unwrap();
+    sixtop.handle_msg(DUMMY_RECEIVER_ADDR, response).unwrap();
 }
@@ -0,0 +1,30 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use sixtop_rs::ie::sixtop_ies;
+use sixtop_rs::msg_builder::serialize_message;
+use sixtop_rs::msg_reader::{deserialize_message, deserialize_response};
+use sixtop_rs::types::{RequestType, SixtopMsg};
+
+/// Whatever parses has to serialize, and parsing the result again has to give the same message.
+/// The input itself may differ from the serialized message, e.g. in the Reserved bits.
+fn check_round_trip(msg: SixtopMsg, parse: impl Fn(Vec<u8>) -> Option<SixtopMsg>) {
+    let data = serialize_message(msg).expect("parsed message doesn't serialize");
+    let reparsed = parse(data.clone()).expect("serialized message doesn't parse");
+    assert_eq!(serialize_message(reparsed).unwrap(), data);
+}
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(msg) = deserialize_message(data.to_vec()) {
+        check_round_trip(msg, |data| deserialize_message(data).ok());
+    }
+
+    for request_type in [RequestType::COUNT, RequestType::SIGNAl].iter() {
+        if let Ok(msg) = deserialize_response(data.to_vec(), *request_type) {
+            check_round_trip(msg, |data| deserialize_response(data, *request_type).ok());
+        }
+    }
+
+    // the same data as a list of Payload IEs
+    for _ in sixtop_ies(data) {}
+});
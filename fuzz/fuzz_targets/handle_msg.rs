@@ -0,0 +1,102 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use std::convert::TryInto;
+
+use sixtop_rs::msg_builder::{write_message, MAX_MSG_SZ_BYTES};
+use sixtop_rs::msg_reader::parse_message;
+use sixtop_rs::scheduling_function::SixtopHandle;
+use sixtop_rs::types::{Cell, CellList, NeighborID, Request, SchedulingFunction, SixtopMsg, SFID};
+use sixtop_rs::Sixtop;
+
+// Proposes the same few cells to everyone, so that 3-step transactions compete for them, and
+// clears the schedule with neighbors whose SeqNum is off.
+struct FuzzSF {}
+
+impl SchedulingFunction for FuzzSF {
+    fn sfid(&self) -> SFID {
+        0
+    }
+
+    fn handle_seqnum_error(&mut self, neighbor: NeighborID, sixtop: &mut SixtopHandle) {
+        sixtop.clear(neighbor).ok();
+    }
+
+    fn propose_cells(&mut self, _neighbor: NeighborID, _request: &Request) -> CellList {
+        (0..4)
+            .map(|slot_offset| Cell {
+                slot_offset,
+                channel_offset: 0,
+            })
+            .collect()
+    }
+}
+
+// The input is a sequence of events, each starting with an opcode byte:
+//
+// 0: neighbor, length, message   the message arrives from the neighbor
+// 1: neighbor, seqnum            the MAC layer acks our last message to the neighbor
+// 2: neighbor, seqnum            the MAC layer nacks our last message to the neighbor
+// 3: time                        time * 100ms pass
+// 4: neighbor, length, message   we start a transaction with the message if it's a request
+//
+// Only 4 different neighbors are used so that their transactions interfere with each other.
+fuzz_target!(|data: &[u8]| {
+    let mut sixtop = Sixtop::new();
+    sixtop.add_scheduling_function(Box::new(FuzzSF {})).unwrap();
+    let mut now = 0;
+    let mut buf = [0; MAX_MSG_SZ_BYTES];
+    let mut input = data.iter().copied();
+
+    while let Some(opcode) = input.next() {
+        let arg = match input.next() {
+            Some(arg) => arg,
+            None => return,
+        };
+        let neighbor = NeighborID::Short(u16::from(arg % 4));
+
+        match opcode % 5 {
+            0 | 4 => {
+                let len = match input.next() {
+                    Some(len) => len as usize,
+                    None => return,
+                };
+                let data: Vec<u8> = input.by_ref().take(len).collect();
+                // messages from the neighbor are read the way Sixtop expects them
+                let msg = if opcode % 5 == 4 {
+                    parse_message(&data)
+                } else {
+                    sixtop.parse_msg(neighbor, &data)
+                };
+                let msg: SixtopMsg = match msg.and_then(|msg| msg.try_into()) {
+                    Ok(msg) => msg,
+                    Err(_) => continue,
+                };
+
+                if opcode % 5 == 4 {
+                    if let SixtopMsg::RequestMsg(request) = msg {
+                        sixtop.start_transaction(neighbor, request, &mut buf).ok();
+                    }
+                } else if let Ok(Some(reply)) = sixtop.handle_msg(neighbor, msg) {
+                    write_message(&reply, &mut buf).expect("reply doesn't fit into a message");
+                }
+            }
+            1 => {
+                if let Some(seqnum) = input.next() {
+                    sixtop.on_link_ack(neighbor, seqnum).ok();
+                }
+            }
+            2 => {
+                if let Some(seqnum) = input.next() {
+                    sixtop.on_link_nack(neighbor, seqnum).ok();
+                }
+            }
+            _ => {
+                now += arg as u64 * 100;
+                sixtop.tick(now);
+            }
+        }
+
+        while sixtop.poll_sf_request(&mut buf).is_some() {}
+    }
+});
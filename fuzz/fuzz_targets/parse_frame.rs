@@ -0,0 +1,19 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use sixtop_rs::frame::parse_frame;
+use sixtop_rs::msg_reader::parse_response;
+use sixtop_rs::types::RequestType;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(frame) = parse_frame(data) {
+        for _ in frame.sixtop_msgs() {}
+
+        // responses to COUNT and SIGNAL requests can only be read knowing the request
+        for msg in frame.raw_sixtop_msgs().flatten() {
+            for request_type in [RequestType::COUNT, RequestType::SIGNAl].iter() {
+                parse_response(msg, *request_type).ok();
+            }
+        }
+    }
+});
@@ -0,0 +1,133 @@
+/**
+ * Link-layer addresses of IEEE 802.15.4 nodes. Neighbors are identified by the address they
+ * send from, which is either a 16-bit short address or their EUI-64.
+ *
+ * Addresses are written as colon-separated bytes, most significant first, e.g. `ab:cd` or
+ * `00:12:4b:00:06:14:2a:3b`. On the air they are sent least significant byte first.
+ */
+use core::fmt;
+use core::str::FromStr;
+
+use crate::error::SixtopError;
+
+const SHORT_ADDR_SZ_BYTES: usize = 2;
+const EXTENDED_ADDR_SZ_BYTES: usize = 8;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash, PartialOrd, Ord)]
+pub enum MacAddress {
+    Short(u16),
+    Extended(u64),
+}
+
+impl fmt::Display for MacAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (bytes, len) = match self {
+            MacAddress::Short(addr) => (u64::from(*addr).to_be_bytes(), SHORT_ADDR_SZ_BYTES),
+            MacAddress::Extended(addr) => (addr.to_be_bytes(), EXTENDED_ADDR_SZ_BYTES),
+        };
+        for (i, byte) in bytes[EXTENDED_ADDR_SZ_BYTES - len..].iter().enumerate() {
+            if i > 0 {
+                write!(f, ":")?;
+            }
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for MacAddress {
+    type Err = SixtopError;
+
+    fn from_str(s: &str) -> Result<MacAddress, SixtopError> {
+        let mut addr: u64 = 0;
+        let mut len = 0;
+        for byte in s.split(':') {
+            // from_str_radix() would also take a sign
+            if byte.len() != 2
+                || !byte.bytes().all(|c| c.is_ascii_hexdigit())
+                || len == EXTENDED_ADDR_SZ_BYTES
+            {
+                return Err(SixtopError::InvalidAddress);
+            }
+            let byte = u8::from_str_radix(byte, 16).map_err(|_| SixtopError::InvalidAddress)?;
+            addr = addr << 8 | u64::from(byte);
+            len += 1;
+        }
+
+        match len {
+            SHORT_ADDR_SZ_BYTES => Ok(MacAddress::Short(addr as u16)),
+            EXTENDED_ADDR_SZ_BYTES => Ok(MacAddress::Extended(addr)),
+            _ => Err(SixtopError::InvalidAddress),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::fmt::Write;
+
+    const TEST_EUI64: u64 = 0x0012_4b00_0614_2a3b;
+
+    #[test]
+    fn test_display_extended() {
+        let mut result = heapless::String::<32>::new();
+
+        // RUN TEST
+        write!(result, "{}", MacAddress::Extended(TEST_EUI64)).unwrap();
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result, "00:12:4b:00:06:14:2a:3b");
+    }
+
+    #[test]
+    fn test_display_short() {
+        let mut result = heapless::String::<32>::new();
+
+        // RUN TEST
+        write!(result, "{}", MacAddress::Short(0x0a0b)).unwrap();
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result, "0a:0b");
+    }
+
+    #[test]
+    fn test_parse() {
+        // RUN TEST
+        let extended = "00:12:4B:00:06:14:2a:3b".parse::<MacAddress>();
+        let short = "ab:cd".parse::<MacAddress>();
+
+        // ASSERT POSTCONDITION
+        assert_eq!(extended, Ok(MacAddress::Extended(TEST_EUI64)));
+        assert_eq!(short, Ok(MacAddress::Short(0xabcd)));
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for test_str in [
+            "",
+            "ab",
+            "ab:cd:ef",
+            "abcd",
+            "00:12:4b:00:06:14:2a:3b:00",
+            "00:12:4b:00:06:14:2a:3",
+            "+1:02",
+            "0g:00",
+        ]
+        .iter()
+        {
+            // RUN TEST
+            let result = test_str.parse::<MacAddress>();
+
+            // ASSERT POSTCONDITION
+            assert_eq!(result, Err(SixtopError::InvalidAddress), "{}", test_str);
+        }
+    }
+
+    #[test]
+    fn test_order() {
+        // ASSERT POSTCONDITION
+        assert!(MacAddress::Short(0xffff) < MacAddress::Extended(0));
+        assert!(MacAddress::Extended(TEST_EUI64) < MacAddress::Extended(TEST_EUI64 + 1));
+    }
+}
@@ -0,0 +1,168 @@
+/**
+ * The CellOptions field of 6P requests, RFC 8480 Section 6.2.3:
+ *
+ *   | Reserved (5 bits) | SHARED | RX | TX |
+ *
+ * The bits are set from the point of view of the node that sends the request. A cell the sender
+ * transmits on is one its neighbor receives on, so the receiver has to swap TX and RX before it
+ * looks at its own schedule, see mirrored().
+ */
+use core::fmt;
+use core::ops::BitOr;
+
+use crate::error::SixtopError;
+
+const RESERVED_MASK: u8 = 0b1111_1000;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default, Hash)]
+pub struct CellOptions {
+    bits: u8,
+}
+
+impl CellOptions {
+    pub const NONE: CellOptions = CellOptions { bits: 0 };
+    pub const TX: CellOptions = CellOptions { bits: 0b001 };
+    pub const RX: CellOptions = CellOptions { bits: 0b010 };
+    pub const SHARED: CellOptions = CellOptions { bits: 0b100 };
+
+    /// returns Err if one of the reserved bits is set
+    pub fn from_u8(value: u8) -> Result<CellOptions, SixtopError> {
+        if value & RESERVED_MASK != 0 {
+            return Err(SixtopError::InvalidCellOptions(value));
+        }
+        Ok(CellOptions { bits: value })
+    }
+
+    pub fn bits(self) -> u8 {
+        self.bits
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.bits == 0
+    }
+
+    /// Check whether all options set in `other` are set in `self` as well.
+    pub fn contains(self, other: CellOptions) -> bool {
+        self.bits & other.bits == other.bits
+    }
+
+    pub fn is_tx(self) -> bool {
+        self.contains(CellOptions::TX)
+    }
+
+    pub fn is_rx(self) -> bool {
+        self.contains(CellOptions::RX)
+    }
+
+    pub fn is_shared(self) -> bool {
+        self.contains(CellOptions::SHARED)
+    }
+
+    /// RFC 8480, Section 3.2.3: the options as seen by the neighbor on the other end of the
+    /// cells, i.e. with TX and RX swapped. SHARED means the same to both.
+    pub fn mirrored(self) -> CellOptions {
+        let mut mirrored = self.bits & CellOptions::SHARED.bits;
+        if self.is_tx() {
+            mirrored |= CellOptions::RX.bits;
+        }
+        if self.is_rx() {
+            mirrored |= CellOptions::TX.bits;
+        }
+        CellOptions { bits: mirrored }
+    }
+
+    /// RFC 8480, Section 3.2.3, Table 1: whether a COUNT or LIST request with these options
+    /// covers a cell scheduled with `scheduled`. Both have to be from the same point of view,
+    /// so the receiver of the request has to mirror them first.
+    ///
+    /// No options at all select every cell, SHARED alone selects all shared cells, anything
+    /// else only selects cells with exactly the same options.
+    pub fn selects(self, scheduled: CellOptions) -> bool {
+        if self.is_empty() {
+            true
+        } else if self == CellOptions::SHARED {
+            scheduled.is_shared()
+        } else {
+            self == scheduled
+        }
+    }
+}
+
+impl BitOr for CellOptions {
+    type Output = CellOptions;
+
+    fn bitor(self, other: CellOptions) -> CellOptions {
+        CellOptions {
+            bits: self.bits | other.bits,
+        }
+    }
+}
+
+impl fmt::Display for CellOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "TX={},RX={},SHARED={}",
+            self.is_tx() as u8,
+            self.is_rx() as u8,
+            self.is_shared() as u8
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_u8() {
+        // RUN TEST
+        let result = CellOptions::from_u8(0b101).unwrap();
+
+        // ASSERT POSTCONDITION
+        assert!(result.is_tx());
+        assert!(!result.is_rx());
+        assert!(result.is_shared());
+        assert_eq!(result, CellOptions::TX | CellOptions::SHARED);
+    }
+
+    #[test]
+    fn test_from_u8_reserved_bits() {
+        // RUN TEST
+        let result = CellOptions::from_u8(0b1001);
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result, Err(SixtopError::InvalidCellOptions(0b1001)));
+    }
+
+    #[test]
+    fn test_mirrored() {
+        // ASSERT POSTCONDITION
+        assert_eq!(CellOptions::TX.mirrored(), CellOptions::RX);
+        assert_eq!(CellOptions::RX.mirrored(), CellOptions::TX);
+        assert_eq!(
+            (CellOptions::TX | CellOptions::SHARED).mirrored(),
+            CellOptions::RX | CellOptions::SHARED
+        );
+        assert_eq!(
+            (CellOptions::TX | CellOptions::RX).mirrored(),
+            CellOptions::TX | CellOptions::RX
+        );
+        assert_eq!(CellOptions::SHARED.mirrored(), CellOptions::SHARED);
+        assert_eq!(CellOptions::NONE.mirrored(), CellOptions::NONE);
+    }
+
+    #[test]
+    fn test_selects() {
+        let tx_shared = CellOptions::TX | CellOptions::SHARED;
+
+        // ASSERT POSTCONDITION
+        assert!(CellOptions::NONE.selects(CellOptions::RX));
+        assert!(CellOptions::SHARED.selects(tx_shared));
+        assert!(!CellOptions::SHARED.selects(CellOptions::TX));
+        assert!(CellOptions::TX.selects(CellOptions::TX));
+        assert!(!CellOptions::TX.selects(tx_shared));
+        assert!(tx_shared.selects(tx_shared));
+        assert!(!tx_shared.selects(CellOptions::TX));
+    }
+}
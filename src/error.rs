@@ -0,0 +1,119 @@
+/**
+ * Everything that can go wrong when parsing, building or processing 6P messages.
+ */
+use core::fmt;
+
+use crate::seqnums::SeqNum;
+use crate::types::{ReturnCode, SFID};
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum SixtopError {
+    /// the message is shorter than the 4 byte 6P header
+    TruncatedHeader,
+    /// the body of the message is shorter than its type requires
+    TruncatedBody,
+    /// a (Candidate/Relocation)CellList ends in the middle of a cell
+    TruncatedCellList,
+    /// the message is longer than its type allows
+    TrailingBytes,
+    /// the CellList or RelocationCellList has fewer than NumCells cells
+    NumCellsMismatch,
+    /// the T field of the header doesn't hold a known message type
+    UnknownMsgType(u8),
+    /// the Code field of a request doesn't hold a known command
+    UnknownRequestType(u8),
+    /// the CellOptions field has reserved bits set
+    InvalidCellOptions(u8),
+    /// the Code field of a response or confirmation doesn't hold a known return code
+    UnknownReturnCode(u8),
+    /// we expected a different message type, e.g. a response but got a request
+    UnexpectedMsgType,
+    /// the Version field of the header isn't the one we implement
+    UnsupportedVersion(u8),
+    /// the message is addressed to an SF we don't know
+    BadSfid(SFID),
+    /// the SeqNum of the message doesn't belong to the transaction or neighbor
+    SeqNumMismatch { expected: SeqNum, received: SeqNum },
+    /// there already is an ongoing transaction with the neighbor
+    Busy,
+    /// there's no ongoing transaction with the neighbor that the message or event fits
+    NoTransaction,
+    /// the neighbor answered with an error return code
+    ErrorResponse(ReturnCode),
+    /// the neighbor's response doesn't make sense for the request
+    InvalidResponse,
+    /// a LIST request has to ask for at least one cell per page
+    ZeroMaxNumCells,
+    /// the cell is already scheduled
+    CellInUse,
+    /// the cell is locked by a transaction with another neighbor
+    CellLocked,
+    /// the Length of an IE goes beyond the end of the frame
+    TruncatedIe,
+    /// the IE isn't an IETF Payload IE with the 6P Sub-ID
+    NotSixtopIe,
+    /// the frame ends before its MAC header or one of its Header IEs
+    TruncatedFrame,
+    /// the frame is secured or uses a reserved addressing mode
+    UnsupportedFrame,
+    /// the frame doesn't carry a source address, so we can't tell which neighbor sent it
+    NoSourceAddress,
+    /// the string isn't a short address or EUI-64 written as colon-separated bytes
+    InvalidAddress,
+    /// the buffer we're supposed to write the message into is too small for it
+    BufferTooSmall,
+    /// a cell list, payload, schedule or neighbor table is full
+    CapacityExceeded,
+}
+
+impl fmt::Display for SixtopError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SixtopError::TruncatedHeader => write!(f, "message too short for a 6P header"),
+            SixtopError::TruncatedBody => write!(f, "message body too short"),
+            SixtopError::TruncatedCellList => write!(f, "cell list ends in the middle of a cell"),
+            SixtopError::TrailingBytes => write!(f, "unexpected bytes at the end of the message"),
+            SixtopError::NumCellsMismatch => write!(f, "fewer cells than NumCells"),
+            SixtopError::UnknownMsgType(msg_type) => write!(f, "unknown message type {}", msg_type),
+            SixtopError::UnknownRequestType(code) => write!(f, "unknown request type {}", code),
+            SixtopError::InvalidCellOptions(cell_options) => {
+                write!(f, "reserved bits set in CellOptions {:#010b}", cell_options)
+            }
+            SixtopError::UnknownReturnCode(code) => write!(f, "unknown return code {}", code),
+            SixtopError::UnexpectedMsgType => write!(f, "unexpected message type"),
+            SixtopError::UnsupportedVersion(version) => {
+                write!(f, "unsupported 6P version {}", version)
+            }
+            SixtopError::BadSfid(sfid) => write!(f, "unknown SFID {}", sfid),
+            SixtopError::SeqNumMismatch { expected, received } => {
+                write!(f, "expected SeqNum {}, received {}", expected, received)
+            }
+            SixtopError::Busy => write!(f, "transaction with neighbor already ongoing"),
+            SixtopError::NoTransaction => write!(f, "no matching transaction with neighbor"),
+            SixtopError::ErrorResponse(code) => write!(f, "neighbor answered with code {}", code),
+            SixtopError::InvalidResponse => write!(f, "invalid response"),
+            SixtopError::ZeroMaxNumCells => write!(f, "LIST request for pages of 0 cells"),
+            SixtopError::CellInUse => write!(f, "cell already scheduled"),
+            SixtopError::CellLocked => write!(f, "cell locked by another transaction"),
+            SixtopError::TruncatedIe => write!(f, "IE longer than the frame"),
+            SixtopError::NotSixtopIe => write!(f, "not a 6P IE"),
+            SixtopError::TruncatedFrame => write!(f, "frame too short for its MAC header"),
+            SixtopError::UnsupportedFrame => write!(f, "unsupported frame"),
+            SixtopError::NoSourceAddress => write!(f, "frame without source address"),
+            SixtopError::InvalidAddress => write!(f, "invalid address"),
+            SixtopError::BufferTooSmall => write!(f, "buffer too small for the message"),
+            SixtopError::CapacityExceeded => write!(f, "out of capacity"),
+        }
+    }
+}
+
+/// On its own, scroll only fails if the buffer is too short for what we read or write, or the
+/// offset lies beyond its end.
+impl From<scroll::Error> for SixtopError {
+    fn from(_: scroll::Error) -> SixtopError {
+        SixtopError::BufferTooSmall
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SixtopError {}
@@ -0,0 +1,469 @@
+/**
+ * Just enough of an IEEE 802.15.4 MAC frame parser to get at the 6P messages in it: the Frame
+ * Control field, the Sequence Number, the PAN IDs and addresses (IEEE 802.15.4-2015 Section 7.2),
+ * the Header IEs and the Payload IEs. parse_sixtop_frame() returns the sender of the frame along
+ * with the first 6P message in it, which Sixtop::parse_msg() turns into what
+ * Sixtop::handle_msg() needs. The message isn't parsed right away because how to read a
+ * response depends on the request it answers, which only Sixtop knows.
+ *
+ * Frames are expected without their FCS. Secured frames aren't supported: without the key, the
+ * Payload IEs can't be read anyway.
+ */
+use scroll::{Pread, LE};
+
+use crate::error::SixtopError;
+use crate::ie::{raw_sixtop_ies, sixtop_ies, RawSixtopIeIter, SixtopIeIter};
+#[cfg(feature = "std")]
+use crate::types::SixtopMsg;
+use crate::types::{MacAddress, NeighborID};
+#[cfg(feature = "std")]
+use crate::Sixtop;
+
+pub const FRAME_CONTROL_SZ_BYTES: usize = 2;
+pub const HEADER_IE_HDR_SZ_BYTES: usize = 2;
+
+pub const FRAME_TYPE_BEACON: u8 = 0b000;
+pub const FRAME_TYPE_DATA: u8 = 0b001;
+pub const FRAME_TYPE_ACK: u8 = 0b010;
+pub const FRAME_TYPE_MAC_CMD: u8 = 0b011;
+
+/// Frame Version of IEEE 802.15.4-2015 frames, the first that may carry IEs
+pub const FRAME_VERSION_2015: u8 = 0b10;
+
+/// Header Termination 1 IE: the Payload IEs follow
+pub const HT1_IE_ID: u8 = 0x7E;
+/// Header Termination 2 IE: the MAC payload follows, there are no Payload IEs
+pub const HT2_IE_ID: u8 = 0x7F;
+
+const FC_FRAME_TYPE_MASK: u16 = 0x0007;
+const FC_SECURITY_ENABLED: u16 = 0x0008;
+const FC_PAN_ID_COMPRESSION: u16 = 0x0040;
+const FC_SEQNUM_SUPPRESSION: u16 = 0x0100;
+const FC_IE_PRESENT: u16 = 0x0200;
+const FC_DST_ADDR_MODE_SHIFT: u16 = 10;
+const FC_FRAME_VERSION_SHIFT: u16 = 12;
+const FC_SRC_ADDR_MODE_SHIFT: u16 = 14;
+
+const ADDR_MODE_NONE: u16 = 0b00;
+const ADDR_MODE_SHORT: u16 = 0b10;
+const ADDR_MODE_EXTENDED: u16 = 0b11;
+
+const HEADER_IE_LENGTH_MASK: u16 = 0x007F;
+const HEADER_IE_ID_MASK: u16 = 0x7F80;
+
+/// An IEEE 802.15.4 frame that borrows everything after its addressing fields from the buffer
+/// it was parsed from.
+#[derive(Debug, PartialEq, Clone)]
+pub struct FrameRef<'a> {
+    pub frame_type: u8,
+    pub frame_version: u8,
+    pub seqnum: Option<u8>,
+    pub dst_pan_id: Option<u16>,
+    pub dst_addr: Option<MacAddress>,
+    pub src_pan_id: Option<u16>,
+    pub src_addr: Option<MacAddress>,
+    /// the Header IEs, including the Header Termination IE
+    pub header_ies: &'a [u8],
+    /// the Payload IEs, followed by the Payload Termination IE and the MAC payload if there is
+    /// one
+    pub payload_ies: &'a [u8],
+    /// the MAC payload if the frame doesn't have Payload IEs, otherwise empty
+    pub payload: &'a [u8],
+}
+
+impl<'a> FrameRef<'a> {
+    /// Iterate over the 6P messages in the Payload IEs, see ie::sixtop_ies().
+    pub fn sixtop_msgs(&self) -> SixtopIeIter<'a> {
+        sixtop_ies(self.payload_ies)
+    }
+
+    /// Iterate over the unparsed 6P messages in the Payload IEs, see ie::raw_sixtop_ies().
+    pub fn raw_sixtop_msgs(&self) -> RawSixtopIeIter<'a> {
+        raw_sixtop_ies(self.payload_ies)
+    }
+}
+
+/// Cursor over the bytes of a frame that reports running past its end as a TruncatedFrame.
+struct FrameReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> FrameReader<'a> {
+    fn read_u8(&mut self) -> Result<u8, SixtopError> {
+        self.data
+            .gread(&mut self.offset)
+            .map_err(|_| SixtopError::TruncatedFrame)
+    }
+
+    fn read_u16(&mut self) -> Result<u16, SixtopError> {
+        self.data
+            .gread_with(&mut self.offset, LE)
+            .map_err(|_| SixtopError::TruncatedFrame)
+    }
+
+    fn read_addr(&mut self, mode: u16) -> Result<Option<MacAddress>, SixtopError> {
+        match mode {
+            ADDR_MODE_NONE => Ok(None),
+            ADDR_MODE_SHORT => Ok(Some(MacAddress::Short(self.read_u16()?))),
+            ADDR_MODE_EXTENDED => {
+                let addr = self
+                    .data
+                    .gread_with(&mut self.offset, LE)
+                    .map_err(|_| SixtopError::TruncatedFrame)?;
+                Ok(Some(MacAddress::Extended(addr)))
+            }
+            _ => Err(SixtopError::UnsupportedFrame),
+        }
+    }
+
+    fn rest(&self) -> &'a [u8] {
+        self.data.get(self.offset..).unwrap_or(&[])
+    }
+}
+
+/// Which of the PAN IDs are present, IEEE 802.15.4-2015 Section 7.2.1.5.
+fn pan_ids_present(
+    frame_version: u8,
+    dst_mode: u16,
+    src_mode: u16,
+    compression: bool,
+) -> (bool, bool) {
+    let has_dst = dst_mode != ADDR_MODE_NONE;
+    let has_src = src_mode != ADDR_MODE_NONE;
+    if frame_version < FRAME_VERSION_2015 {
+        return (has_dst, has_src && !compression);
+    }
+    match (has_dst, has_src) {
+        (false, false) => (compression, false),
+        (true, false) => (!compression, false),
+        (false, true) => (false, !compression),
+        (true, true) => {
+            if dst_mode == ADDR_MODE_EXTENDED && src_mode == ADDR_MODE_EXTENDED {
+                (!compression, false)
+            } else {
+                (true, !compression)
+            }
+        }
+    }
+}
+
+/// Split the Header IEs off the start of `data`. Returns the Header IEs, the rest of the frame
+/// and whether the rest starts with Payload IEs.
+fn split_header_ies(data: &[u8]) -> Result<(&[u8], &[u8], bool), SixtopError> {
+    let mut offset = 0;
+    while offset < data.len() {
+        let descriptor: u16 = data
+            .pread_with(offset, LE)
+            .map_err(|_| SixtopError::TruncatedFrame)?;
+        let length = (descriptor & HEADER_IE_LENGTH_MASK) as usize;
+        let element_id = ((descriptor & HEADER_IE_ID_MASK) >> 7) as u8;
+        offset += HEADER_IE_HDR_SZ_BYTES + length;
+        if offset > data.len() {
+            return Err(SixtopError::TruncatedFrame);
+        }
+        if element_id == HT1_IE_ID || element_id == HT2_IE_ID {
+            let (header_ies, rest) = data.split_at(offset);
+            return Ok((header_ies, rest, element_id == HT1_IE_ID));
+        }
+    }
+    // without a Header Termination IE, the Header IEs extend to the end of the frame
+    Ok((data, &[], false))
+}
+
+/// Parse the MAC header of an IEEE 802.15.4 frame without its FCS.
+pub fn parse_frame(data: &[u8]) -> Result<FrameRef<'_>, SixtopError> {
+    let mut reader = FrameReader { data, offset: 0 };
+    let frame_control = reader.read_u16()?;
+    if frame_control & FC_SECURITY_ENABLED != 0 {
+        return Err(SixtopError::UnsupportedFrame);
+    }
+
+    let frame_version = ((frame_control >> FC_FRAME_VERSION_SHIFT) & 0b11) as u8;
+    let dst_mode = (frame_control >> FC_DST_ADDR_MODE_SHIFT) & 0b11;
+    let src_mode = (frame_control >> FC_SRC_ADDR_MODE_SHIFT) & 0b11;
+    let (has_dst_pan_id, has_src_pan_id) = pan_ids_present(
+        frame_version,
+        dst_mode,
+        src_mode,
+        frame_control & FC_PAN_ID_COMPRESSION != 0,
+    );
+
+    let mut frame = FrameRef {
+        frame_type: (frame_control & FC_FRAME_TYPE_MASK) as u8,
+        frame_version,
+        seqnum: None,
+        dst_pan_id: None,
+        dst_addr: None,
+        src_pan_id: None,
+        src_addr: None,
+        header_ies: &[],
+        payload_ies: &[],
+        payload: &[],
+    };
+    // the Sequence Number Suppression bit is reserved before IEEE 802.15.4-2015
+    if frame_version < FRAME_VERSION_2015 || frame_control & FC_SEQNUM_SUPPRESSION == 0 {
+        frame.seqnum = Some(reader.read_u8()?);
+    }
+    if has_dst_pan_id {
+        frame.dst_pan_id = Some(reader.read_u16()?);
+    }
+    frame.dst_addr = reader.read_addr(dst_mode)?;
+    if has_src_pan_id {
+        frame.src_pan_id = Some(reader.read_u16()?);
+    }
+    frame.src_addr = reader.read_addr(src_mode)?;
+
+    if frame_version >= FRAME_VERSION_2015 && frame_control & FC_IE_PRESENT != 0 {
+        let (header_ies, rest, has_payload_ies) = split_header_ies(reader.rest())?;
+        frame.header_ies = header_ies;
+        if has_payload_ies {
+            frame.payload_ies = rest;
+        } else {
+            frame.payload = rest;
+        }
+    } else {
+        frame.payload = reader.rest();
+    }
+
+    Ok(frame)
+}
+
+/// Parse an IEEE 802.15.4 frame without its FCS and return its source address and the first
+/// 6P message in its Payload IEs, still unparsed. Sixtop::parse_msg() reads the message.
+pub fn parse_sixtop_frame(data: &[u8]) -> Result<(NeighborID, &[u8]), SixtopError> {
+    let frame = parse_frame(data)?;
+    let src_addr = frame.src_addr.ok_or(SixtopError::NoSourceAddress)?;
+    let msg = frame
+        .raw_sixtop_msgs()
+        .next()
+        .unwrap_or(Err(SixtopError::NotSixtopIe))?;
+    Ok((src_addr, msg))
+}
+
+/// Parse an IEEE 802.15.4 frame and the 6P message in it the way `sixtop` expects it, see
+/// parse_sixtop_frame() and Sixtop::parse_msg().
+#[cfg(feature = "std")]
+pub fn deserialize_sixtop_frame(
+    sixtop: &Sixtop,
+    data: Vec<u8>,
+) -> Result<(NeighborID, SixtopMsg), SixtopError> {
+    use core::convert::TryInto;
+
+    let (src_addr, msg) = parse_sixtop_frame(&data)?;
+    Ok((src_addr, sixtop.parse_msg(src_addr, msg)?.try_into()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ie::SIXTOP_SUBIE_ID;
+    use crate::msg_builder::MAX_MSG_SZ_BYTES;
+    use crate::msg_reader::parse_message;
+    use crate::types::{CellOptions, MsgType, RequestType, ReturnCode, SixtopMsg, DEFAULT_SFID};
+    use crate::Sixtop;
+    use core::convert::TryInto;
+
+    const TEST_SEQNUM: u8 = 3;
+    const TEST_SRC_ADDR: u64 = 0x0012_4b00_0614_2a3b;
+
+    /// Data frame, AR, PAN ID Compression, IE Present, short destination address, 2015 frame
+    /// version, extended source address
+    const TEST_FRAME_CONTROL: [u8; 2] = [0b0110_0001, 0b1110_1010];
+
+    /// Data frame from TEST_SRC_ADDR to 0x0001 in PAN 0xabcd with a CLEAR request
+    const TEST_FRAME: [u8; 30] = [
+        TEST_FRAME_CONTROL[0],
+        TEST_FRAME_CONTROL[1],
+        0x17, // Sequence Number
+        0xcd,
+        0xab, // Destination PAN ID
+        0x01,
+        0x00, // Destination Address
+        0x3b,
+        0x2a,
+        0x14,
+        0x06,
+        0x00,
+        0x4b,
+        0x12,
+        0x00, // Source Address
+        0x00,
+        0x3f, // HT1 IE
+        0b0000_0111,
+        0b1010_1000,
+        SIXTOP_SUBIE_ID,
+        0b0000_0000,
+        RequestType::CLEAR.to_u8(),
+        DEFAULT_SFID,
+        TEST_SEQNUM,
+        0b0000_0000,
+        0b0000_0000, // IETF IE with a CLEAR request
+        0x00,
+        0xf8, // Payload Termination IE
+        0x42,
+        0x42, // MAC payload
+    ];
+
+    #[test]
+    fn test_parse_frame() {
+        // RUN TEST
+        let result = parse_frame(&TEST_FRAME).unwrap();
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result.frame_type, FRAME_TYPE_DATA);
+        assert_eq!(result.frame_version, FRAME_VERSION_2015);
+        assert_eq!(result.seqnum, Some(0x17));
+        assert_eq!(result.dst_pan_id, Some(0xabcd));
+        assert_eq!(result.dst_addr, Some(MacAddress::Short(0x0001)));
+        assert_eq!(result.src_pan_id, None);
+        assert_eq!(result.src_addr, Some(MacAddress::Extended(TEST_SRC_ADDR)));
+        assert_eq!(result.header_ies, [0x00, 0x3f]);
+        assert_eq!(result.payload_ies, &TEST_FRAME[17..]);
+        assert!(result.payload.is_empty());
+    }
+
+    #[test]
+    fn test_parse_sixtop_frame() {
+        // RUN TEST
+        let (src_addr, msg) = parse_sixtop_frame(&TEST_FRAME).unwrap();
+
+        // ASSERT POSTCONDITION
+        assert_eq!(src_addr, MacAddress::Extended(TEST_SRC_ADDR));
+        assert_eq!(msg, &TEST_FRAME[20..26]);
+        let header = parse_message(msg).unwrap().header();
+        assert_eq!(header.msg_type, MsgType::REQUEST);
+        assert_eq!(header.code, RequestType::CLEAR.to_u8());
+        assert_eq!(header.seqnum, TEST_SEQNUM);
+    }
+
+    #[test]
+    fn test_parse_2006_frame() {
+        // the 2006 frame version, short addresses, both PAN IDs present
+        let test_frame = [
+            0b0000_0001,
+            0b1001_1000,
+            0x17,
+            0xcd,
+            0xab,
+            0x01,
+            0x00,
+            0xcd,
+            0xab,
+            0x02,
+            0x00,
+            0x42,
+        ];
+
+        // RUN TEST
+        let result = parse_frame(&test_frame).unwrap();
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result.src_pan_id, Some(0xabcd));
+        assert_eq!(result.src_addr, Some(MacAddress::Short(0x0002)));
+        assert!(result.header_ies.is_empty());
+        assert_eq!(result.payload, [0x42]);
+    }
+
+    #[test]
+    fn test_parse_frame_skips_header_ies() {
+        let mut test_frame = [0; 34];
+        test_frame[..15].copy_from_slice(&TEST_FRAME[..15]);
+        // Time Correction IE (ID 0x1e, Length 2)
+        test_frame[15..19].copy_from_slice(&[0x02, 0x0f, 0x00, 0x00]);
+        test_frame[19..].copy_from_slice(&TEST_FRAME[15..]);
+
+        // RUN TEST
+        let (src_addr, msg) = parse_sixtop_frame(&test_frame).unwrap();
+
+        // ASSERT POSTCONDITION
+        assert_eq!(src_addr, MacAddress::Extended(TEST_SRC_ADDR));
+        assert_eq!(msg, &TEST_FRAME[20..26]);
+    }
+
+    #[test]
+    fn test_parse_frame_truncated() {
+        // RUN TEST
+        let result = parse_frame(&TEST_FRAME[..10]);
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result, Err(SixtopError::TruncatedFrame));
+    }
+
+    #[test]
+    fn test_parse_frame_secured() {
+        let mut test_frame = TEST_FRAME;
+        test_frame[0] |= 0b0000_1000;
+
+        // RUN TEST
+        let result = parse_frame(&test_frame);
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result, Err(SixtopError::UnsupportedFrame));
+    }
+
+    #[test]
+    fn test_parse_sixtop_frame_without_sixtop_ie() {
+        // RUN TEST
+        let result = parse_sixtop_frame(&TEST_FRAME[..17]);
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result, Err(SixtopError::NotSixtopIe));
+    }
+
+    #[test]
+    fn test_handle_sixtop_frame() {
+        let mut sixtop = Sixtop::new();
+        let (sender, msg) = parse_sixtop_frame(&TEST_FRAME).unwrap();
+
+        // RUN TEST
+        let msg = sixtop.parse_msg(sender, msg).unwrap();
+        let result = sixtop.handle_msg(sender, msg.try_into().unwrap()).unwrap();
+
+        // ASSERT POSTCONDITION
+        if let Some(SixtopMsg::ResponseMsg(response)) = result {
+            assert_eq!(response.header.code, ReturnCode::RC_SUCCESS);
+            assert_eq!(response.header.seqnum, TEST_SEQNUM);
+        } else {
+            panic!("expected a response");
+        }
+    }
+
+    #[test]
+    fn test_handle_count_response_frame() {
+        let mut sixtop = Sixtop::new();
+        let sender = MacAddress::Extended(TEST_SRC_ADDR);
+        sixtop
+            .count_cells(sender, CellOptions::TX, &mut [0; MAX_MSG_SZ_BYTES])
+            .unwrap();
+        let mut test_frame = [0; 28];
+        test_frame[..17].copy_from_slice(&TEST_FRAME[..17]);
+        test_frame[17..].copy_from_slice(&[
+            0b0000_0111,
+            0b1010_1000,
+            SIXTOP_SUBIE_ID,
+            0b0000_0100,
+            ReturnCode::RC_SUCCESS.to_u8(),
+            DEFAULT_SFID,
+            0,
+            0x05,
+            0x00, // IETF IE with a COUNT response, NumCells 5
+            0x00,
+            0xf8, // Payload Termination IE
+        ]);
+
+        // RUN TEST
+        let (src_addr, msg) = parse_sixtop_frame(&test_frame).unwrap();
+        let msg = sixtop.parse_msg(src_addr, msg).unwrap();
+
+        // ASSERT POSTCONDITION
+        assert_eq!(src_addr, sender);
+        let msg: SixtopMsg = msg.try_into().unwrap();
+        if let SixtopMsg::CountResponseMsg(response) = &msg {
+            assert_eq!(response.num_cells, 5);
+        } else {
+            panic!("expected a count response");
+        }
+        assert!(sixtop.handle_msg(src_addr, msg).unwrap().is_none());
+    }
+}
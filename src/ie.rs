@@ -0,0 +1,347 @@
+/**
+ * IEEE 802.15.4 framing of 6P messages. On the air, a 6P message is carried in a Payload IE of
+ * the IETF group, which holds the 6P Sub-ID followed by the message (RFC 8480 Section 3.1,
+ * RFC 8137 Section 3):
+ *
+ *   | Length (11 bits) | Group ID (4 bits) | Type (1 bit) | Sub-ID (1 byte) | 6P message |
+ *
+ * The Length covers the Sub-ID and the message. An IETF IE has no room for a second Sub-ID, so
+ * a frame with several sub-IEs carries several IETF IEs in its list of Payload IEs; sixtop_ies()
+ * walks that list and yields every 6P message in it, skipping the IEs of other groups and
+ * sub-IEs.
+ */
+use scroll::ctx::{TryFromCtx, TryIntoCtx};
+use scroll::{Pread, Pwrite, LE};
+
+use crate::error::SixtopError;
+use crate::msg_reader::parse_message;
+use crate::msg_ref::SixtopMsgRef;
+use crate::types::SixtopMsg;
+
+pub const PAYLOAD_IE_HDR_SZ_BYTES: usize = 2;
+pub const SUBIE_ID_SZ_BYTES: usize = 1;
+/// Group ID of IETF IEs, IEEE 802.15.4-2015 Table 7-15
+pub const IETF_IE_GROUP_ID: u8 = 0x5;
+/// Group ID of the Payload Termination IE, which ends the list of Payload IEs
+pub const PAYLOAD_TERMINATION_IE_GROUP_ID: u8 = 0xF;
+/// IANA_6TOP_SUBIE_ID, RFC 8480 Section 6.1
+pub const SIXTOP_SUBIE_ID: u8 = 0xC9;
+
+const IE_LENGTH_MASK: u16 = 0x07FF;
+const IE_GROUP_ID_MASK: u16 = 0x7800;
+const IE_TYPE_PAYLOAD: u16 = 0x8000;
+
+/// Header of a Payload IE. `length` is the size of the IE content, without the header.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct PayloadIeHdr {
+    pub length: u16,
+    pub group_id: u8,
+}
+
+impl PayloadIeHdr {
+    pub fn new(group_id: u8, length: u16) -> PayloadIeHdr {
+        PayloadIeHdr { length, group_id }
+    }
+}
+
+impl TryIntoCtx for &PayloadIeHdr {
+    type Error = SixtopError;
+
+    fn try_into_ctx(self, dst: &mut [u8], _: ()) -> Result<usize, SixtopError> {
+        if self.length > IE_LENGTH_MASK {
+            return Err(SixtopError::CapacityExceeded);
+        }
+        let descriptor = IE_TYPE_PAYLOAD
+            | ((self.group_id as u16) << 11) & IE_GROUP_ID_MASK
+            | self.length & IE_LENGTH_MASK;
+        Ok(dst.pwrite_with(descriptor, 0, LE)?)
+    }
+}
+
+impl<'a> TryFromCtx<'a> for PayloadIeHdr {
+    type Error = SixtopError;
+
+    fn try_from_ctx(src: &'a [u8], _: ()) -> Result<(PayloadIeHdr, usize), SixtopError> {
+        let descriptor: u16 = src
+            .pread_with(0, LE)
+            .map_err(|_| SixtopError::TruncatedIe)?;
+        // Header IEs use a different layout, they don't belong into the list of Payload IEs
+        if descriptor & IE_TYPE_PAYLOAD == 0 {
+            return Err(SixtopError::NotSixtopIe);
+        }
+        let header = PayloadIeHdr {
+            length: descriptor & IE_LENGTH_MASK,
+            group_id: ((descriptor & IE_GROUP_ID_MASK) >> 11) as u8,
+        };
+        Ok((header, PAYLOAD_IE_HDR_SZ_BYTES))
+    }
+}
+
+/// Split the Payload IE at the start of `data` into its header and content.
+fn split_ie(data: &[u8]) -> Result<(PayloadIeHdr, &[u8]), SixtopError> {
+    let (header, _) = PayloadIeHdr::try_from_ctx(data, ())?;
+    let content = data
+        .get(PAYLOAD_IE_HDR_SZ_BYTES..PAYLOAD_IE_HDR_SZ_BYTES + header.length as usize)
+        .ok_or(SixtopError::TruncatedIe)?;
+    Ok((header, content))
+}
+
+/// Returns the 6P message in the content of an IETF IE, or None if it holds another sub-IE.
+fn sixtop_subie(content: &[u8]) -> Option<&[u8]> {
+    match content.split_first() {
+        Some((&SIXTOP_SUBIE_ID, msg)) => Some(msg),
+        _ => None,
+    }
+}
+
+/// Write `msg` wrapped into an IETF IE with the 6P Sub-ID to the start of `buf`. Returns the
+/// number of bytes written, including the IE header.
+pub fn write_sixtop_ie(msg: &SixtopMsg, buf: &mut [u8]) -> Result<usize, SixtopError> {
+    // the Length is only known once the message is written, so the header goes in last
+    let mut offset = PAYLOAD_IE_HDR_SZ_BYTES;
+    buf.gwrite(SIXTOP_SUBIE_ID, &mut offset)?;
+    buf.gwrite(msg, &mut offset)?;
+
+    let length = (offset - PAYLOAD_IE_HDR_SZ_BYTES) as u16;
+    buf.pwrite(&PayloadIeHdr::new(IETF_IE_GROUP_ID, length), 0)?;
+    Ok(offset)
+}
+
+/// Unwrap the 6P message in the IETF IE at the start of `data` without parsing it. Returns the
+/// message and the size of the whole IE; bytes after the IE are left alone.
+pub fn split_sixtop_ie(data: &[u8]) -> Result<(&[u8], usize), SixtopError> {
+    let (header, content) = split_ie(data)?;
+    if header.group_id != IETF_IE_GROUP_ID {
+        return Err(SixtopError::NotSixtopIe);
+    }
+    let msg = sixtop_subie(content).ok_or(SixtopError::NotSixtopIe)?;
+    Ok((msg, PAYLOAD_IE_HDR_SZ_BYTES + content.len()))
+}
+
+/// Parse the IETF IE at the start of `data` without copying the 6P message out of it, see
+/// msg_reader::parse_message(). Returns the message and the size of the whole IE; bytes after
+/// the IE are left alone.
+///
+/// Responses to COUNT and SIGNAL requests can only be read with split_sixtop_ie() and
+/// Sixtop::parse_msg().
+pub fn parse_sixtop_ie(data: &[u8]) -> Result<(SixtopMsgRef<'_>, usize), SixtopError> {
+    let (msg, len) = split_sixtop_ie(data)?;
+    Ok((parse_message(msg)?, len))
+}
+
+/// Parse an IETF IE, see parse_sixtop_ie().
+#[cfg(feature = "std")]
+pub fn deserialize_sixtop_ie(data: Vec<u8>) -> Result<SixtopMsg, SixtopError> {
+    use core::convert::TryInto;
+
+    parse_sixtop_ie(&data)?.0.try_into()
+}
+
+/// Iterate over the 6P messages in a list of Payload IEs, see sixtop_ies().
+#[derive(Debug, Clone)]
+pub struct SixtopIeIter<'a> {
+    raw: RawSixtopIeIter<'a>,
+}
+
+impl<'a> Iterator for SixtopIeIter<'a> {
+    type Item = Result<SixtopMsgRef<'a>, SixtopError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.raw.next()?.and_then(parse_message))
+    }
+}
+
+/// Iterate over the unparsed 6P messages in a list of Payload IEs, see raw_sixtop_ies().
+#[derive(Debug, Clone)]
+pub struct RawSixtopIeIter<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for RawSixtopIeIter<'a> {
+    type Item = Result<&'a [u8], SixtopError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.data.is_empty() {
+            let (header, content) = match split_ie(self.data) {
+                Ok(ie) => ie,
+                Err(err) => {
+                    // we can't tell where the next IE starts, so this is the last one
+                    self.data = &[];
+                    return Some(Err(err));
+                }
+            };
+            if header.group_id == PAYLOAD_TERMINATION_IE_GROUP_ID {
+                self.data = &[];
+                return None;
+            }
+            self.data = &self.data[PAYLOAD_IE_HDR_SZ_BYTES + content.len()..];
+
+            if header.group_id == IETF_IE_GROUP_ID {
+                if let Some(msg) = sixtop_subie(content) {
+                    return Some(Ok(msg));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Walk the list of Payload IEs in `data` up to the Payload Termination IE or the end of `data`
+/// and yield every 6P message in it. A malformed 6P message is yielded as an error and the walk
+/// goes on with the next IE; a truncated IE ends it.
+pub fn sixtop_ies(data: &[u8]) -> SixtopIeIter<'_> {
+    SixtopIeIter {
+        raw: raw_sixtop_ies(data),
+    }
+}
+
+/// Like sixtop_ies(), but yields the 6P messages without parsing them, e.g. for
+/// Sixtop::parse_msg(). Only a truncated IE is yielded as an error.
+pub fn raw_sixtop_ies(data: &[u8]) -> RawSixtopIeIter<'_> {
+    RawSixtopIeIter { data }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msg_builder::MAX_MSG_SZ_BYTES;
+    use crate::types::{Msg, MsgType, Request, RequestType, ResponseHdr, DEFAULT_SFID};
+
+    const TEST_SEQNUM: u8 = 3;
+
+    /// CLEAR request in an IETF IE: Length 7, Group ID 0x5, Type 1
+    const TEST_CLEAR_IE: [u8; 9] = [
+        0b0000_0111,
+        0b1010_1000,
+        SIXTOP_SUBIE_ID,
+        0b0000_0000,
+        RequestType::CLEAR.to_u8(),
+        DEFAULT_SFID,
+        TEST_SEQNUM,
+        0b0000_0000,
+        0b0000_0000,
+    ];
+
+    fn test_clear_request() -> SixtopMsg {
+        let mut request = Request::new();
+        request.header.code = RequestType::CLEAR;
+        request.header.seqnum = TEST_SEQNUM;
+        SixtopMsg::RequestMsg(request)
+    }
+
+    #[test]
+    fn test_write_sixtop_ie() {
+        let mut buf = [0; PAYLOAD_IE_HDR_SZ_BYTES + SUBIE_ID_SZ_BYTES + MAX_MSG_SZ_BYTES];
+
+        // RUN TEST
+        let result = write_sixtop_ie(&test_clear_request(), &mut buf);
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result, Ok(TEST_CLEAR_IE.len()));
+        assert_eq!(buf[..TEST_CLEAR_IE.len()], TEST_CLEAR_IE);
+    }
+
+    #[test]
+    fn test_write_sixtop_ie_buffer_too_small() {
+        let mut buf = [0; 8];
+
+        // RUN TEST
+        let result = write_sixtop_ie(&test_clear_request(), &mut buf);
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result, Err(SixtopError::BufferTooSmall));
+    }
+
+    #[test]
+    fn test_parse_sixtop_ie() {
+        let mut test_frame = [0xFF; 11];
+        test_frame[..TEST_CLEAR_IE.len()].copy_from_slice(&TEST_CLEAR_IE);
+
+        // RUN TEST
+        let (msg, len) = parse_sixtop_ie(&test_frame).unwrap();
+
+        // ASSERT POSTCONDITION
+        assert_eq!(len, TEST_CLEAR_IE.len());
+        assert_eq!(msg.header().seqnum, TEST_SEQNUM);
+        assert_eq!(msg.header().code, RequestType::CLEAR.to_u8());
+    }
+
+    #[test]
+    fn test_parse_sixtop_ie_truncated() {
+        // RUN TEST
+        let result = parse_sixtop_ie(&TEST_CLEAR_IE[..8]);
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result, Err(SixtopError::TruncatedIe));
+    }
+
+    #[test]
+    fn test_parse_sixtop_ie_other_subie() {
+        let mut test_ie = TEST_CLEAR_IE;
+        test_ie[2] = 0x01;
+
+        // RUN TEST
+        let result = parse_sixtop_ie(&test_ie);
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result, Err(SixtopError::NotSixtopIe));
+    }
+
+    #[test]
+    fn test_sixtop_ies() {
+        let mut response = ResponseHdr::new(MsgType::RESPONSE);
+        response.seqnum = TEST_SEQNUM + 1;
+        let test_frame = [
+            // MLME IE, Group ID 0x1, which isn't ours
+            0b0000_0001,
+            0b1000_1000,
+            0x42,
+            // CLEAR request
+            TEST_CLEAR_IE[0],
+            TEST_CLEAR_IE[1],
+            TEST_CLEAR_IE[2],
+            TEST_CLEAR_IE[3],
+            TEST_CLEAR_IE[4],
+            TEST_CLEAR_IE[5],
+            TEST_CLEAR_IE[6],
+            TEST_CLEAR_IE[7],
+            TEST_CLEAR_IE[8],
+            // empty response: Length 5
+            0b0000_0101,
+            0b1010_1000,
+            SIXTOP_SUBIE_ID,
+            response.to_bytes()[0],
+            response.to_bytes()[1],
+            response.to_bytes()[2],
+            response.to_bytes()[3],
+            // Payload Termination IE, followed by the MAC payload
+            0b0000_0000,
+            0b1111_1000,
+            0b0000_0110,
+            0b1010_1000,
+        ];
+
+        // RUN TEST
+        let result: heapless::Vec<_, 4> = sixtop_ies(&test_frame).collect();
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].as_ref().unwrap().header().seqnum, TEST_SEQNUM);
+        assert_eq!(
+            result[1].as_ref().unwrap().header().msg_type,
+            MsgType::RESPONSE
+        );
+    }
+
+    #[test]
+    fn test_sixtop_ies_truncated() {
+        let test_frame = [0b0000_0111, 0b1010_1000, SIXTOP_SUBIE_ID];
+
+        // RUN TEST
+        let mut result = sixtop_ies(&test_frame);
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result.next(), Some(Err(SixtopError::TruncatedIe)));
+        assert_eq!(result.next(), None);
+    }
+}
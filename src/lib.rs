@@ -1,21 +1,499 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+/// Progress messages, passed on to the log facade at debug level if the "log" feature is on.
+/// Without it, the arguments are only evaluated.
+#[cfg(feature = "log")]
+macro_rules! log {
+    ($($arg:tt)*) => {
+        log::debug!($($arg)*)
+    };
+}
+#[cfg(not(feature = "log"))]
+macro_rules! log {
+    ($fmt:expr $(, $arg:expr)*) => {{
+        $(let _ = &$arg;)*
+    }};
+}
+
+pub mod address;
+pub mod cell_options;
+pub mod error;
+pub mod frame;
+pub mod ie;
+pub mod list_pager;
 pub mod msg_builder;
 pub mod msg_reader;
+pub mod msg_ref;
+pub mod schedule;
+pub mod scheduling_function;
 pub mod seqnums;
+pub mod transaction;
 pub mod types;
 
-use crate::seqnums::{SeqNums, START_SEQNUM};
-use crate::types::{Msg, NeighborID, Response, ReturnCode, SixtopMsg};
-
+use heapless::LinearMap;
 
+use crate::error::SixtopError;
+use crate::msg_builder::write_request;
+use crate::msg_reader::{parse_message, parse_response};
+use crate::msg_ref::SixtopMsgRef;
+use crate::schedule::Schedule;
+use crate::scheduling_function::{
+    SchedulingFunction, SfRequests, SixtopHandle, TransactionOutcome,
+};
+use crate::seqnums::{SeqNum, SeqNums, START_SEQNUM};
+use crate::transaction::{
+    changes_schedule, is_three_step_request, request_processed, Role, Timeouts, Timestamp,
+    Transaction, TransactionState,
+};
+use crate::types::{
+    Cell, CellList, CellOptions, Confirmation, CountResponse, DynSchedulingFunction, Msg, MsgHdr,
+    NeighborID, Payload, Request, RequestType, Response, ResponseHdr, ReturnCode, SignalResponse,
+    SixtopMsg, DEFAULT_SFID, MAX_NEIGHBORS, MAX_NUM_CELLS, MAX_SCHEDULING_FUNCTIONS, SFID,
+    SIXTOP_VERSION,
+};
 
 pub struct Sixtop {
     seqnums: SeqNums,
+    schedule: Schedule,
+    transactions: LinearMap<NeighborID, Transaction, MAX_NEIGHBORS>,
+    timeouts: Timeouts,
+    /// time of the last call to tick()
+    now: Timestamp,
+    /// the SF for each SFID we speak. Requests are handled by the SF named in their header.
+    scheduling_functions: LinearMap<SFID, DynSchedulingFunction, MAX_SCHEDULING_FUNCTIONS>,
+    /// transactions the SFs asked for, see poll_sf_request()
+    sf_requests: SfRequests,
+    /// SFID and Metadata we put in the requests we send. We answer requests for our own SFID
+    /// even if there's no SF for it.
+    sfid: SFID,
+    metadata: u16,
+}
+
+impl Default for Sixtop {
+    fn default() -> Sixtop {
+        Sixtop::new()
+    }
 }
 
 impl Sixtop {
     pub fn new() -> Sixtop {
         Sixtop {
             seqnums: SeqNums::new(),
+            schedule: Schedule::new(),
+            transactions: LinearMap::new(),
+            timeouts: Timeouts::default(),
+            now: 0,
+            scheduling_functions: LinearMap::new(),
+            sf_requests: SfRequests::new(),
+            sfid: DEFAULT_SFID,
+            metadata: 0,
+        }
+    }
+
+    pub fn set_timeouts(&mut self, timeouts: Timeouts) {
+        self.timeouts = timeouts;
+    }
+
+    /// Handle the requests for the SFID of `scheduling_function` with it, replacing the SF that
+    /// handled them so far.
+    ///
+    /// returns Err if there already are MAX_SCHEDULING_FUNCTIONS other SFs
+    pub fn add_scheduling_function(
+        &mut self,
+        scheduling_function: DynSchedulingFunction,
+    ) -> Result<(), SixtopError> {
+        self.scheduling_functions
+            .insert(scheduling_function.sfid(), scheduling_function)
+            .map_err(|_| SixtopError::CapacityExceeded)?;
+        Ok(())
+    }
+
+    /// Call `f` with the SF for `sfid` and a handle it can start transactions with.
+    ///
+    /// returns None if there's no SF for `sfid`
+    fn with_scheduling_function<R>(
+        &mut self,
+        sfid: SFID,
+        f: impl FnOnce(&mut dyn SchedulingFunction, &mut SixtopHandle) -> R,
+    ) -> Option<R> {
+        let scheduling_function = self.scheduling_functions.get_mut(&sfid)?;
+        let mut sixtop = SixtopHandle::new(sfid, self.metadata, &mut self.sf_requests);
+        Some(f(&mut **scheduling_function, &mut sixtop))
+    }
+
+    fn notify_outcome(
+        &mut self,
+        neighbor: NeighborID,
+        request: &Request,
+        outcome: TransactionOutcome,
+    ) {
+        self.with_scheduling_function(request.header.sfid, |scheduling_function, sixtop| {
+            scheduling_function.handle_outcome(neighbor, request, outcome, sixtop)
+        });
+    }
+
+    /// Check whether we answer requests for `sfid`, see RFC 8480, Section 3.4.5.
+    fn knows_sfid(&self, sfid: SFID) -> bool {
+        sfid == self.sfid || self.scheduling_functions.contains_key(&sfid)
+    }
+
+    pub fn set_sfid(&mut self, sfid: SFID) {
+        self.sfid = sfid;
+    }
+
+    pub fn set_metadata(&mut self, metadata: u16) {
+        self.metadata = metadata;
+    }
+
+    pub fn schedule(&self) -> &Schedule {
+        &self.schedule
+    }
+
+    pub fn transaction_state(&self, neighbor: NeighborID) -> TransactionState {
+        match self.transactions.get(&neighbor) {
+            Some(transaction) => transaction.state,
+            None => TransactionState::Idle,
+        }
+    }
+
+    /// The SeqNum of the ongoing transaction with `neighbor`, which is what the MAC layer has
+    /// to pass to on_link_ack() and on_link_nack().
+    pub fn transaction_seqnum(&self, neighbor: NeighborID) -> Option<SeqNum> {
+        self.transactions
+            .get(&neighbor)
+            .map(|transaction| transaction.request.header.seqnum)
+    }
+
+    /// The type of the request we sent to `neighbor` if we're waiting for its response.
+    pub fn pending_request_type(&self, neighbor: NeighborID) -> Option<RequestType> {
+        self.transactions
+            .get(&neighbor)
+            .filter(|transaction| transaction.state == TransactionState::WaitingForResponse)
+            .map(|transaction| transaction.request.header.code)
+    }
+
+    /// Parse the 6P message `data` that `sender` sent us. What the body of a response looks
+    /// like depends on the request it answers, so a response from a neighbor we're waiting for
+    /// is parsed as the answer to our request; everything else goes to
+    /// msg_reader::parse_message().
+    pub fn parse_msg<'a>(
+        &self,
+        sender: NeighborID,
+        data: &'a [u8],
+    ) -> Result<SixtopMsgRef<'a>, SixtopError> {
+        match self.pending_request_type(sender) {
+            Some(request_type) => match parse_response(data, request_type) {
+                Err(SixtopError::UnexpectedMsgType) => parse_message(data),
+                result => result,
+            },
+            None => parse_message(data),
+        }
+    }
+
+    /// Let time pass. Transactions that didn't progress before their timeout expired are
+    /// aborted, and the SF is notified about each of them.
+    ///
+    /// Timeouts are measured from the last call to tick() before the transaction changed its
+    /// state, so this should be called regularly.
+    ///
+    /// returns the neighbors whose transactions were aborted
+    pub fn tick(&mut self, now: Timestamp) -> heapless::Vec<NeighborID, MAX_NEIGHBORS> {
+        self.now = now;
+
+        let expired: heapless::Vec<NeighborID, MAX_NEIGHBORS> = self
+            .transactions
+            .iter()
+            .filter(|(_, transaction)| transaction.is_expired(now))
+            .map(|(neighbor, _)| *neighbor)
+            .collect();
+
+        for neighbor in expired.iter() {
+            let transaction = self.transactions.remove(neighbor).unwrap();
+            log!("6top transaction with {} timed out", neighbor);
+            self.finish_transaction(*neighbor, &transaction);
+
+            let request = &transaction.request;
+            self.with_scheduling_function(request.header.sfid, |scheduling_function, sixtop| {
+                scheduling_function.handle_timeout(*neighbor, request, sixtop)
+            });
+        }
+
+        expired
+    }
+
+    /// Start a transaction with `neighbor` by sending `request` to it. The SeqNum of `request`
+    /// is overwritten with the one we use with `neighbor`, its SFID names the SF the transaction
+    /// belongs to. The cells in its (Candidate)CellList and RelocationCellList are locked until
+    /// the transaction is over.
+    ///
+    /// returns the length of the request, which was written to `buf`,
+    ///         Err if there already is an ongoing transaction with `neighbor`, we don't know the
+    ///         SFID, one of the cells is locked by another transaction or `buf` is too small
+    pub fn start_transaction(
+        &mut self,
+        neighbor: NeighborID,
+        mut request: Request,
+        buf: &mut [u8],
+    ) -> Result<usize, SixtopError> {
+        if self.transactions.contains_key(&neighbor) {
+            return Err(SixtopError::Busy);
+        }
+
+        if !self.knows_sfid(request.header.sfid) {
+            return Err(SixtopError::BadSfid(request.header.sfid));
+        }
+
+        request.header.seqnum = self.seqnums.guaranteed_get_seqnum(neighbor)?;
+
+        let len = write_request(&request, buf)?;
+        if changes_schedule(&request) {
+            self.schedule.lock_cells(
+                request.metadata,
+                neighbor,
+                &cells_to_lock(&request, &request.cell_list),
+            )?;
+        }
+
+        let mut transaction = Transaction::new(Role::Initiator, request);
+        transaction.set_state(
+            TransactionState::WaitingForResponse,
+            self.now,
+            &self.timeouts,
+        );
+        self.insert_transaction(neighbor, transaction);
+        Ok(len)
+    }
+
+    /// Ask `neighbor` to add `num_cells` cells. If `candidates` is empty, `neighbor` proposes
+    /// the cells and we pick from them (3-step transaction).
+    pub fn add_cells(
+        &mut self,
+        neighbor: NeighborID,
+        cell_options: CellOptions,
+        num_cells: u8,
+        candidates: &[Cell],
+        buf: &mut [u8],
+    ) -> Result<usize, SixtopError> {
+        let request = cell_request(
+            self.new_request(RequestType::ADD),
+            cell_options,
+            num_cells,
+            candidates,
+        )?;
+        self.start_transaction(neighbor, request, buf)
+    }
+
+    /// Ask `neighbor` to delete `num_cells` of the `candidates` we have scheduled with it.
+    pub fn delete_cells(
+        &mut self,
+        neighbor: NeighborID,
+        cell_options: CellOptions,
+        num_cells: u8,
+        candidates: &[Cell],
+        buf: &mut [u8],
+    ) -> Result<usize, SixtopError> {
+        let request = cell_request(
+            self.new_request(RequestType::DELETE),
+            cell_options,
+            num_cells,
+            candidates,
+        )?;
+        self.start_transaction(neighbor, request, buf)
+    }
+
+    /// Ask `neighbor` to move the cells in `relocation_cell_list` to some of the `candidates`.
+    pub fn relocate_cells(
+        &mut self,
+        neighbor: NeighborID,
+        cell_options: CellOptions,
+        relocation_cell_list: &[Cell],
+        candidates: &[Cell],
+        buf: &mut [u8],
+    ) -> Result<usize, SixtopError> {
+        let request = relocate_request(
+            self.new_request(RequestType::RELOCATE),
+            cell_options,
+            relocation_cell_list,
+            candidates,
+        )?;
+        self.start_transaction(neighbor, request, buf)
+    }
+
+    /// Ask `neighbor` how many cells with `cell_options` it has scheduled with us.
+    pub fn count_cells(
+        &mut self,
+        neighbor: NeighborID,
+        cell_options: CellOptions,
+        buf: &mut [u8],
+    ) -> Result<usize, SixtopError> {
+        let request = count_request(self.new_request(RequestType::COUNT), cell_options);
+        self.start_transaction(neighbor, request, buf)
+    }
+
+    /// Ask `neighbor` for up to `max_num_cells` of the cells with `cell_options` it has
+    /// scheduled with us, starting at `offset`. ListPager keeps track of the pages.
+    pub fn list_cells(
+        &mut self,
+        neighbor: NeighborID,
+        cell_options: CellOptions,
+        offset: u16,
+        max_num_cells: u16,
+        buf: &mut [u8],
+    ) -> Result<usize, SixtopError> {
+        let request = list_request(
+            self.new_request(RequestType::LIST),
+            cell_options,
+            offset,
+            max_num_cells,
+        );
+        self.start_transaction(neighbor, request, buf)
+    }
+
+    /// Pass `payload` to the SF of `neighbor`.
+    pub fn signal(
+        &mut self,
+        neighbor: NeighborID,
+        payload: &[u8],
+        buf: &mut [u8],
+    ) -> Result<usize, SixtopError> {
+        let request = signal_request(self.new_request(RequestType::SIGNAl), payload)?;
+        self.start_transaction(neighbor, request, buf)
+    }
+
+    /// Ask `neighbor` to drop all cells it has scheduled with us. We drop them as soon as we
+    /// get its response, whatever it says (RFC 8480, Section 3.3.6).
+    pub fn clear(&mut self, neighbor: NeighborID, buf: &mut [u8]) -> Result<usize, SixtopError> {
+        let request = self.new_request(RequestType::CLEAR);
+        self.start_transaction(neighbor, request, buf)
+    }
+
+    /// Forget everything about `neighbor`, e.g. once it left the network: the cells we have
+    /// scheduled with it, its SeqNum and the ongoing transaction with it. If we started that
+    /// transaction, its SF learns so through TransactionOutcome::Cleared.
+    ///
+    /// We only keep track of MAX_NEIGHBORS neighbors, and requests from others are answered
+    /// with RC_ERR, so neighbors that are gone should be removed.
+    pub fn remove_neighbor(&mut self, neighbor: NeighborID) {
+        if let Some(transaction) = self.transactions.remove(&neighbor) {
+            if transaction.role == Role::Initiator {
+                let outcome = TransactionOutcome::Cleared;
+                self.notify_outcome(neighbor, &transaction.request, outcome);
+            }
+        }
+        self.schedule.remove_neighbor(neighbor);
+        self.seqnums.remove_neighbor(neighbor);
+    }
+
+    /// Every neighbor with a transaction has a SeqNum entry and there's room for as many
+    /// transactions as SeqNum entries, so this can't fail.
+    fn insert_transaction(&mut self, neighbor: NeighborID, transaction: Transaction) {
+        self.transactions.insert(neighbor, transaction).ok();
+    }
+
+    fn new_request(&self, code: RequestType) -> Request {
+        new_request(code, self.sfid, self.metadata)
+    }
+
+    /// Start the next transaction an SF asked for through its SixtopHandle. If that fails, the
+    /// SF is told so with TransactionOutcome::NotStarted.
+    ///
+    /// returns the neighbor and the length of the request, which was written to `buf`, or why
+    ///         the transaction couldn't be started,
+    ///         None if no SF is waiting for a transaction
+    pub fn poll_sf_request(
+        &mut self,
+        buf: &mut [u8],
+    ) -> Option<(NeighborID, Result<usize, SixtopError>)> {
+        let (neighbor, request) = self.sf_requests.pop_front()?;
+        let result = self.start_transaction(neighbor, request.clone(), buf);
+        if let Err(error) = result {
+            self.notify_outcome(neighbor, &request, TransactionOutcome::NotStarted(error));
+        }
+        Some((neighbor, result))
+    }
+
+    /// The MAC layer reports that the 6P message with `seqnum` it sent to `neighbor` was
+    /// acknowledged.
+    ///
+    /// Once the last message of an ADD, DELETE or RELOCATE transaction has been acknowledged,
+    /// its cells are locked in and the transaction ends.
+    ///
+    /// returns Err if there's no ongoing transaction with `neighbor` that `seqnum` belongs to
+    pub fn on_link_ack(&mut self, neighbor: NeighborID, seqnum: SeqNum) -> Result<(), SixtopError> {
+        let transaction = self
+            .transactions
+            .get_mut(&neighbor)
+            .ok_or(SixtopError::NoTransaction)?;
+        if transaction.request.header.seqnum != seqnum {
+            return Err(SixtopError::SeqNumMismatch {
+                expected: transaction.request.header.seqnum,
+                received: seqnum,
+            });
+        }
+
+        match transaction.state {
+            TransactionState::WaitingForResponse => transaction.request_acked = true,
+            TransactionState::WaitingForLinkAck => {
+                let transaction = self.transactions.remove(&neighbor).unwrap();
+                self.commit_cells(neighbor, &transaction, &transaction.cell_list);
+                self.finish_transaction(neighbor, &transaction);
+                if transaction.role == Role::Initiator {
+                    // our confirmation made it
+                    let outcome = TransactionOutcome::Answered {
+                        code: ReturnCode::RC_SUCCESS,
+                        cell_list: &transaction.cell_list,
+                    };
+                    self.notify_outcome(neighbor, &transaction.request, outcome);
+                }
+            }
+            // whether our response arrived is decided by the confirmation (or its absence)
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// The MAC layer reports that the 6P message with `seqnum` it sent to `neighbor` could not
+    /// be delivered. The transaction is aborted without changing the schedule.
+    ///
+    /// returns Err if there's no ongoing transaction with `neighbor` that `seqnum` belongs to
+    pub fn on_link_nack(
+        &mut self,
+        neighbor: NeighborID,
+        seqnum: SeqNum,
+    ) -> Result<(), SixtopError> {
+        let transaction = self
+            .transactions
+            .get(&neighbor)
+            .ok_or(SixtopError::NoTransaction)?;
+        if transaction.request.header.seqnum != seqnum {
+            return Err(SixtopError::SeqNumMismatch {
+                expected: transaction.request.header.seqnum,
+                received: seqnum,
+            });
+        }
+
+        match transaction.state {
+            TransactionState::WaitingForResponse | TransactionState::WaitingForLinkAck => {
+                let transaction = self.transactions.remove(&neighbor).unwrap();
+                log!("6top transaction with {} aborted", neighbor);
+                self.finish_transaction(neighbor, &transaction);
+                if transaction.role == Role::Initiator {
+                    let outcome = TransactionOutcome::NotDelivered;
+                    self.notify_outcome(neighbor, &transaction.request, outcome);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// RFC 8480, Section 3.4.6: the SeqNum is incremented by exactly 1 at the end of a
+    /// transaction. We only do this if the neighbor has seen the request, because otherwise it
+    /// won't increment its SeqNum either.
+    fn finish_transaction(&mut self, neighbor: NeighborID, transaction: &Transaction) {
+        self.schedule.unlock_cells(neighbor);
+        if transaction.role == Role::Responder || transaction.request_acked {
+            self.seqnums.increment_seqnum(neighbor);
         }
     }
 
@@ -23,73 +501,2844 @@ impl Sixtop {
         &mut self,
         sender: NeighborID,
         msg: SixtopMsg,
-    ) -> Result<Option<SixtopMsg>, ()> {
+    ) -> Result<Option<SixtopMsg>, SixtopError> {
+        let header = msg.header();
+        if header.version != SIXTOP_VERSION {
+            return match msg {
+                SixtopMsg::RequestMsg(_) => Ok(Some(version_error_response(&header))),
+                _ => Err(SixtopError::UnsupportedVersion(header.version)),
+            };
+        }
+
         match msg {
-            SixtopMsg::RequestMsg(request) => {
-                let mut response = Response::new();
-
-                match self.seqnums.verify(sender, request.header.seqnum) {
-                    Ok(seqnum) => {
-                        response.header.code = ReturnCode::RC_SUCCESS as u8;
-                        response.header.seqnum = seqnum;
-
-                        // DUMMY: just choose the first two cells. obvs missing coherence check etc.
-                        // Proper pick should be done by the SF.
-                        for index in 0..request.num_cells {
-                            response
-                                .cell_list
-                                .push(*request.cell_list.get(index as usize).unwrap());
-                        }
-                        // TODO lock in cells in schedule
+            SixtopMsg::RequestMsg(request) => Ok(Some(self.handle_request(sender, request))),
+            SixtopMsg::ResponseMsg(response) => self.handle_response(
+                sender,
+                &response.header,
+                ResponseBody::CellList(&response.cell_list),
+            ),
+            SixtopMsg::CountResponseMsg(response) => self.handle_response(
+                sender,
+                &response.header,
+                ResponseBody::NumCells(response.num_cells),
+            ),
+            SixtopMsg::SignalResponseMsg(response) => self.handle_response(
+                sender,
+                &response.header,
+                ResponseBody::Payload(&response.payload),
+            ),
+            SixtopMsg::ConfirmationMsg(confirmation) => {
+                self.handle_confirmation(sender, confirmation)
+            }
+        }
+    }
 
-                        // TODO this is not the right way to do this: "if node A receives the link-layer
-                        // acknowledgment for its 6P Request, it will increment the SeqNum by exactly 1
-                        // after the 6P Transaction ends."
-                        self.seqnums.increment_seqnum(sender);
-                    }
-                    Err(_) => {
-                        // inconsistency detected
-                        println!("inconsistency detected");
-                        response.header.code = ReturnCode::RC_ERR_SEQNUM as u8;
-
-                        // as per the instructions on p. 34, but
-                        // not sure if this is correct– p. 30 of RFC8480 contradicts this:
-                        // "In this 6P Response or 6P Confirmation, the SeqNum field MUST be set to
-                        // the value of the sender of the message (0 in the example in Figure 31)."
-                        response.header.seqnum = START_SEQNUM;
-
-                        // TODO notify SF: The SF of node A MAY decide what to do next,
-                        // as described in Section 3.4.6.2.
+    fn handle_request(&mut self, sender: NeighborID, request: Request) -> SixtopMsg {
+        let sfid = request.header.sfid;
+        if !self.scheduling_functions.contains_key(&sfid) {
+            return self.answer_request(sender, request);
+        }
+
+        let reply = self.answer_request(sender, request.clone());
+        let code = ReturnCode::from_wire(reply.header().code);
+        self.with_scheduling_function(sfid, |scheduling_function, sixtop| {
+            scheduling_function.handle_request(sender, &request, code, sixtop)
+        });
+        reply
+    }
+
+    fn answer_request(&mut self, sender: NeighborID, request: Request) -> SixtopMsg {
+        let mut response = Response::new();
+        response.header.sfid = request.header.sfid;
+
+        if !self.knows_sfid(request.header.sfid) {
+            // RFC 8480, Section 3.4.5: no SF to handle the request
+            response.header.code = ReturnCode::RC_ERR_SFID;
+            response.header.seqnum = request.header.seqnum;
+            return SixtopMsg::ResponseMsg(response);
+        }
+
+        if let RequestType::CLEAR = request.header.code {
+            // CLEAR always succeeds, even if the SeqNum doesn't match what we expect
+            self.handle_clear_request(sender, &request, &mut response);
+            return SixtopMsg::ResponseMsg(response);
+        }
+
+        if self.transactions.contains_key(&sender) {
+            // RFC 8480, Section 3.4.3: only one transaction per pair of neighbors at a time
+            response.header.code = ReturnCode::RC_ERR_BUSY;
+            response.header.seqnum = request.header.seqnum;
+            return SixtopMsg::ResponseMsg(response);
+        }
+
+        match self.seqnums.verify(sender, request.header.seqnum) {
+            Ok(seqnum) => response.header.seqnum = seqnum,
+            Err(SixtopError::CapacityExceeded) => {
+                // we can't keep track of another neighbor
+                response.header.code = ReturnCode::RC_ERR;
+                response.header.seqnum = request.header.seqnum;
+                return SixtopMsg::ResponseMsg(response);
+            }
+            Err(_) => {
+                // inconsistency detected
+                log!("inconsistency detected");
+                response.header.code = ReturnCode::RC_ERR_SEQNUM;
+
+                // as per the instructions on p. 34, but
+                // not sure if this is correct– p. 30 of RFC8480 contradicts this:
+                // "In this 6P Response or 6P Confirmation, the SeqNum field MUST be set to
+                // the value of the sender of the message (0 in the example in Figure 31)."
+                response.header.seqnum = START_SEQNUM;
+
+                self.with_scheduling_function(
+                    request.header.sfid,
+                    |scheduling_function, sixtop| {
+                        scheduling_function.handle_seqnum_error(sender, sixtop)
                     },
+                );
+                return SixtopMsg::ResponseMsg(response);
+            }
+        }
+
+        if is_three_step_request(&request) {
+            return self.handle_three_step_request(sender, request, response);
+        }
+
+        let reply = match request.header.code {
+            RequestType::ADD => {
+                self.handle_add_request(sender, &request, &mut response);
+                SixtopMsg::ResponseMsg(response)
+            }
+            RequestType::DELETE => {
+                self.handle_delete_request(sender, &request, &mut response);
+                SixtopMsg::ResponseMsg(response)
+            }
+            RequestType::RELOCATE => {
+                self.handle_relocate_request(sender, &request, &mut response);
+                SixtopMsg::ResponseMsg(response)
+            }
+            RequestType::LIST => {
+                self.handle_list_request(sender, &request, &mut response);
+                SixtopMsg::ResponseMsg(response)
+            }
+            RequestType::SIGNAl => SixtopMsg::SignalResponseMsg(self.handle_signal_request(
+                sender,
+                &request,
+                response.header,
+            )),
+            RequestType::COUNT => SixtopMsg::CountResponseMsg(self.handle_count_request(
+                sender,
+                &request,
+                response.header,
+            )),
+            _ => {
+                response.header.code = ReturnCode::RC_ERR;
+                SixtopMsg::ResponseMsg(response)
+            }
+        };
+
+        match &reply {
+            SixtopMsg::ResponseMsg(response)
+                if changes_schedule(&request) && response.header.code == ReturnCode::RC_SUCCESS =>
+            {
+                // the cells are only locked in once `sender` got our response, see on_link_ack().
+                // The handlers only pick cells that aren't locked, so this only fails if there's
+                // no room for more locks, in which case the transaction goes on without them.
+                self.schedule
+                    .lock_cells(
+                        request.metadata,
+                        sender,
+                        &cells_to_lock(&request, &response.cell_list),
+                    )
+                    .ok();
+                let mut transaction = Transaction::new(Role::Responder, request);
+                transaction.cell_list = response.cell_list.clone();
+                transaction.set_state(
+                    TransactionState::WaitingForLinkAck,
+                    self.now,
+                    &self.timeouts,
+                );
+                self.insert_transaction(sender, transaction);
+            }
+            // nothing changes for us, the transaction ends with our response
+            reply if request_processed(ReturnCode::from_wire(reply.header().code)) => {
+                self.seqnums.increment_seqnum(sender)
+            }
+            _ => {}
+        }
+
+        reply
+    }
+
+    fn handle_response(
+        &mut self,
+        sender: NeighborID,
+        header: &ResponseHdr,
+        body: ResponseBody,
+    ) -> Result<Option<SixtopMsg>, SixtopError> {
+        let mut transaction = self
+            .take_transaction(sender, TransactionState::WaitingForResponse)
+            .ok_or(SixtopError::NoTransaction)?;
+        // an answer means that our request made it, even if we never saw the link-layer ack
+        transaction.request_acked = true;
+
+        if header.code == ReturnCode::RC_ERR_SEQNUM {
+            // `sender` didn't process our request, so it didn't increment its SeqNum either.
+            // The SF decides what to do next, RFC 8480 Section 3.4.6.2.
+            log!("inconsistency detected");
+            self.schedule.unlock_cells(sender);
+            let sfid = transaction.request.header.sfid;
+            self.with_scheduling_function(sfid, |scheduling_function, sixtop| {
+                scheduling_function.handle_seqnum_error(sender, sixtop)
+            });
+            return Ok(None);
+        }
+
+        if header.seqnum != transaction.request.header.seqnum {
+            // not the response we're waiting for
+            let expected = transaction.request.header.seqnum;
+            transaction.request_acked = false;
+            self.insert_transaction(sender, transaction);
+            return Err(SixtopError::SeqNumMismatch {
+                expected,
+                received: header.seqnum,
+            });
+        }
+
+        let body = match body.expect(transaction.request.header.code) {
+            Ok(body) => body,
+            Err(error) => {
+                // `sender` did see our request, but this can't be the answer to it
+                self.insert_transaction(sender, transaction);
+                return Err(error);
+            }
+        };
+        if !request_processed(header.code) {
+            // `sender` didn't increment its SeqNum, so we don't either
+            transaction.request_acked = false;
+        }
+        let success = header.code == ReturnCode::RC_SUCCESS || header.code == ReturnCode::RC_EOL;
+
+        let cell_list = match body {
+            ResponseBody::CellList(cell_list) => cell_list,
+            ResponseBody::NumCells(num_cells) => {
+                // COUNT doesn't change the schedule, the transaction ends here
+                self.finish_transaction(sender, &transaction);
+                log!("6top TRANSACTION COMPLETE");
+                let outcome = TransactionOutcome::Counted {
+                    code: header.code,
+                    num_cells: if success { num_cells } else { 0 },
+                };
+                self.notify_outcome(sender, &transaction.request, outcome);
+                return Ok(None);
+            }
+            ResponseBody::Payload(payload) => {
+                // only the SF knows what the payload means, the transaction ends here
+                self.finish_transaction(sender, &transaction);
+                log!("6top TRANSACTION COMPLETE");
+                let outcome = TransactionOutcome::Signaled {
+                    code: header.code,
+                    payload,
+                };
+                self.notify_outcome(sender, &transaction.request, outcome);
+                return Ok(None);
+            }
+        };
+        // the CellList of error responses doesn't mean anything
+        let mut cell_list = if success { cell_list } else { &[] };
+
+        if let RequestType::CLEAR = transaction.request.header.code {
+            // the transaction is already gone, so the SF only hears about it once
+            self.remove_neighbor(sender);
+            log!("6top TRANSACTION COMPLETE");
+            let outcome = TransactionOutcome::Answered {
+                code: header.code,
+                cell_list: &[],
+            };
+            self.notify_outcome(sender, &transaction.request, outcome);
+            return Ok(None);
+        }
+
+        if header.code == ReturnCode::RC_SUCCESS && transaction.is_three_step() {
+            if let Some(confirmation) = self.confirm_cells(sender, &transaction, header, cell_list)
+            {
+                // the cells are only locked in once `sender` got our confirmation,
+                // see on_link_ack(). confirm_cells() only picks unlocked cells, so this only
+                // fails if there's no room for more locks.
+                self.schedule
+                    .lock_cells(
+                        transaction.request.metadata,
+                        sender,
+                        &confirmation.cell_list,
+                    )
+                    .ok();
+                transaction.cell_list = confirmation.cell_list.clone();
+                transaction.set_state(
+                    TransactionState::WaitingForLinkAck,
+                    self.now,
+                    &self.timeouts,
+                );
+                self.insert_transaction(sender, transaction);
+                return Ok(Some(SixtopMsg::ConfirmationMsg(confirmation)));
+            }
+            // nothing to confirm, the transaction ends without cells
+            cell_list = &[];
+        } else if header.code == ReturnCode::RC_SUCCESS {
+            self.commit_cells(sender, &transaction, cell_list);
+        }
+
+        self.finish_transaction(sender, &transaction);
+        log!("6top TRANSACTION COMPLETE");
+        let outcome = TransactionOutcome::Answered {
+            code: header.code,
+            cell_list,
+        };
+        self.notify_outcome(sender, &transaction.request, outcome);
+
+        Ok(None)
+    }
+
+    /// Second step of a 3-step transaction we initiated: pick from the cells `sender` proposed
+    /// and tell it which ones we chose.
+    fn confirm_cells(
+        &mut self,
+        sender: NeighborID,
+        transaction: &Transaction,
+        header: &ResponseHdr,
+        candidate_cell_list: &[Cell],
+    ) -> Option<Confirmation> {
+        if candidate_cell_list.is_empty() {
+            // nothing to pick from, the transaction ends here
+            return None;
+        }
+
+        let request = &transaction.request;
+        let usable_cell_list: CellList = candidate_cell_list
+            .iter()
+            .filter(|cell| match request.header.code {
+                RequestType::DELETE => {
+                    self.schedule.is_scheduled_with(
+                        request.metadata,
+                        cell,
+                        sender,
+                        request.cell_options,
+                    ) && !self.schedule.is_locked(request.metadata, cell)
                 }
+                _ => self.schedule.is_available(request.metadata, cell),
+            })
+            .take(MAX_NUM_CELLS)
+            .copied()
+            .collect();
+        let cell_list = self.pick_cells(sender, request, &usable_cell_list);
+
+        let mut confirmation = Confirmation::new();
+        confirmation.header.code = ReturnCode::RC_SUCCESS;
+        confirmation.header.sfid = header.sfid;
+        confirmation.header.seqnum = header.seqnum;
+        confirmation.cell_list = cell_list;
+        Some(confirmation)
+    }
 
-                Ok(Some(SixtopMsg::ResponseMsg(response)))
+    /// Let the SF of `request` pick up to NumCells of the `usable_cell_list`; without an SF, we
+    /// just take the first ones. Cells the SF picks twice or that aren't usable are dropped.
+    fn pick_cells(
+        &mut self,
+        neighbor: NeighborID,
+        request: &Request,
+        usable_cell_list: &CellList,
+    ) -> CellList {
+        let picked_cell_list = self
+            .with_scheduling_function(request.header.sfid, |scheduling_function, _| {
+                scheduling_function.pick_cells(neighbor, request, usable_cell_list)
+            })
+            .unwrap_or_else(|| usable_cell_list.clone());
+        let mut cell_list = CellList::new();
+        for cell in picked_cell_list.iter() {
+            if cell_list.len() == request.num_cells as usize {
+                break;
             }
-            SixtopMsg::ResponseMsg(response) => {
-                match self.seqnums.verify(sender, response.header.seqnum) {
-                    Ok(_) => {
-                        // TODO lock in cells in schedule
+            // can't fail, there are no more distinct usable cells than fit into a CellList
+            if usable_cell_list.contains(cell) && !cell_list.contains(cell) {
+                cell_list.push(*cell).ok();
+            }
+        }
+        cell_list
+    }
+
+    /// Last step of a 3-step transaction `sender` initiated: lock in the cells it picked from
+    /// the ones we proposed.
+    fn handle_confirmation(
+        &mut self,
+        sender: NeighborID,
+        confirmation: Confirmation,
+    ) -> Result<Option<SixtopMsg>, SixtopError> {
+        let transaction = self
+            .take_transaction(sender, TransactionState::WaitingForConfirmation)
+            .ok_or(SixtopError::NoTransaction)?;
+
+        if confirmation.header.seqnum != transaction.request.header.seqnum {
+            // not the confirmation we're waiting for
+            let expected = transaction.request.header.seqnum;
+            self.insert_transaction(sender, transaction);
+            return Err(SixtopError::SeqNumMismatch {
+                expected,
+                received: confirmation.header.seqnum,
+            });
+        }
 
-                        // TODO this is not the right way to do this: "if node A receives the link-layer
-                        // acknowledgment for its 6P Request, it will increment the SeqNum by exactly 1
-                        // after the 6P Transaction ends."
-                        self.seqnums.increment_seqnum(sender);
+        if confirmation.header.code == ReturnCode::RC_SUCCESS {
+            let cell_list: CellList = confirmation
+                .cell_list
+                .iter()
+                .filter(|cell| transaction.candidate_cell_list.contains(cell))
+                .take(transaction.request.num_cells as usize)
+                .copied()
+                .collect();
+            self.commit_cells(sender, &transaction, &cell_list);
+        }
+
+        self.finish_transaction(sender, &transaction);
+        log!("6top TRANSACTION COMPLETE");
+
+        Ok(None)
+    }
 
-                        println!("6top TRANSACTION COMPLETE");
+    /// Remove the ongoing transaction with `neighbor` if it is in the given `state`.
+    fn take_transaction(
+        &mut self,
+        neighbor: NeighborID,
+        state: TransactionState,
+    ) -> Option<Transaction> {
+        match self.transactions.get(&neighbor) {
+            Some(transaction) if transaction.state == state => self.transactions.remove(&neighbor),
+            _ => None,
+        }
+    }
 
-                        Ok(None)
+    /// Apply the outcome of an ADD, DELETE or RELOCATE transaction with `neighbor` to our
+    /// schedule. `cell_list` contains the cells both sides agreed on.
+    fn commit_cells(
+        &mut self,
+        neighbor: NeighborID,
+        transaction: &Transaction,
+        cell_list: &[Cell],
+    ) {
+        let request = &transaction.request;
+        let slotframe = request.metadata;
+        let cell_options = transaction.cell_options();
+        match request.header.code {
+            RequestType::ADD => {
+                for cell in cell_list {
+                    // cells we can't use anymore are ignored
+                    self.schedule
+                        .add_cell(slotframe, *cell, neighbor, cell_options)
+                        .ok();
+                }
+            }
+            RequestType::DELETE => {
+                for cell in cell_list {
+                    if self
+                        .schedule
+                        .is_scheduled_with(slotframe, cell, neighbor, cell_options)
+                    {
+                        self.schedule.remove_cell(slotframe, cell);
+                    }
+                }
+            }
+            RequestType::RELOCATE => {
+                if let Some(relocation_cell_list) = &request.relocation_cell_list {
+                    for (old_cell, new_cell) in relocation_cell_list.iter().zip(cell_list.iter()) {
+                        if self.schedule.is_scheduled(slotframe, new_cell) {
+                            continue;
+                        }
+                        self.schedule.remove_cell(slotframe, old_cell);
+                        self.schedule
+                            .add_cell(slotframe, *new_cell, neighbor, cell_options)
+                            .ok();
                     }
-                    Err(_) => { unimplemented!() }
                 }
             }
+            _ => {}
+        }
+    }
+
+    /// First step of a 3-step transaction `sender` initiated: propose candidate cells and wait
+    /// for its confirmation.
+    fn handle_three_step_request(
+        &mut self,
+        sender: NeighborID,
+        request: Request,
+        mut response: Response,
+    ) -> SixtopMsg {
+        let slotframe = request.metadata;
+        let candidate_cell_list: CellList = match request.header.code {
+            RequestType::DELETE => self
+                .schedule
+                .list_cells(slotframe, sender, request.cell_options.mirrored())
+                .into_iter()
+                .filter(|cell| !self.schedule.is_locked(slotframe, cell))
+                .take(MAX_NUM_CELLS)
+                .collect(),
+            RequestType::RELOCATE if !self.is_relocatable(sender, &request) => {
+                response.header.code = ReturnCode::RC_ERR_CELLLIST;
+                self.seqnums.increment_seqnum(sender);
+                return SixtopMsg::ResponseMsg(response);
+            }
+            RequestType::RELOCATE if self.is_relocation_locked(&request) => {
+                response.header.code = ReturnCode::RC_ERR_LOCKED;
+                self.seqnums.increment_seqnum(sender);
+                return SixtopMsg::ResponseMsg(response);
+            }
+            _ => self
+                .with_scheduling_function(request.header.sfid, |scheduling_function, _| {
+                    scheduling_function.propose_cells(sender, &request)
+                })
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|cell| self.schedule.is_available(slotframe, cell))
+                .collect(),
+        };
+
+        response.header.code = ReturnCode::RC_SUCCESS;
+        response.cell_list = candidate_cell_list.clone();
+
+        if candidate_cell_list.is_empty() {
+            // there's nothing to confirm, the transaction ends here
+            self.seqnums.increment_seqnum(sender);
+        } else {
+            // the transaction ends with the confirmation, which is when we increment the SeqNum
+            self.schedule
+                .lock_cells(
+                    slotframe,
+                    sender,
+                    &cells_to_lock(&request, &candidate_cell_list),
+                )
+                .ok();
+            let mut transaction = Transaction::new(Role::Responder, request);
+            transaction.candidate_cell_list = candidate_cell_list;
+            transaction.set_state(
+                TransactionState::WaitingForConfirmation,
+                self.now,
+                &self.timeouts,
+            );
+            self.insert_transaction(sender, transaction);
+        }
+
+        SixtopMsg::ResponseMsg(response)
+    }
+
+    fn handle_add_request(
+        &mut self,
+        sender: NeighborID,
+        request: &Request,
+        response: &mut Response,
+    ) {
+        self.pick_candidates(sender, request, response);
+    }
+
+    /// Let the SF of `request` pick up to NumCells of the free cells in its CandidateCellList.
+    /// If there are none because other transactions have locked the candidates, we answer with
+    /// RC_ERR_LOCKED so that `sender` can try again later.
+    fn pick_candidates(&mut self, sender: NeighborID, request: &Request, response: &mut Response) {
+        let slotframe = request.metadata;
+        let mut free_cell_list = CellList::new();
+        for cell in request.cell_list.iter() {
+            // can't fail, free_cell_list holds as many cells as the CandidateCellList
+            if self.schedule.is_available(slotframe, cell) && !free_cell_list.contains(cell) {
+                free_cell_list.push(*cell).ok();
+            }
+        }
+        response.cell_list = self.pick_cells(sender, request, &free_cell_list);
+
+        let locked = request
+            .cell_list
+            .iter()
+            .any(|cell| self.schedule.is_locked(slotframe, cell));
+        response.header.code = if free_cell_list.is_empty() && request.num_cells > 0 && locked {
+            ReturnCode::RC_ERR_LOCKED
+        } else {
+            ReturnCode::RC_SUCCESS
+        };
+    }
+
+    /// RFC 8480, Section 3.3.2: all cells in the CellList have to be scheduled with `sender`
+    /// using the requested cell options, mirrored to our point of view. If that's not the case,
+    /// nothing is deleted and we answer with RC_ERR_CELLLIST.
+    fn handle_delete_request(
+        &self,
+        sender: NeighborID,
+        request: &Request,
+        response: &mut Response,
+    ) {
+        let slotframe = request.metadata;
+        let num_cells = request.num_cells as usize;
+        let all_scheduled = request.cell_list.iter().all(|cell| {
+            self.schedule.is_scheduled_with(
+                slotframe,
+                cell,
+                sender,
+                request.cell_options.mirrored(),
+            )
+        });
+
+        if !all_scheduled || request.cell_list.len() < num_cells {
+            response.header.code = ReturnCode::RC_ERR_CELLLIST;
+            return;
+        }
+
+        let to_delete = &request.cell_list[..num_cells];
+        if to_delete
+            .iter()
+            .any(|cell| self.schedule.is_locked(slotframe, cell))
+        {
+            response.header.code = ReturnCode::RC_ERR_LOCKED;
+            return;
+        }
+
+        // can't fail, `to_delete` is part of a CellList
+        response.cell_list.extend_from_slice(to_delete).ok();
+
+        response.header.code = ReturnCode::RC_SUCCESS;
+    }
+
+    /// RFC 8480, Section 3.3.4: count the cells we have scheduled with `sender` that the
+    /// requested cell options select, see CellOptions::selects().
+    fn handle_count_request(
+        &self,
+        sender: NeighborID,
+        request: &Request,
+        header: ResponseHdr,
+    ) -> CountResponse {
+        let mut response = CountResponse::new();
+        response.header = header;
+        response.header.code = ReturnCode::RC_SUCCESS;
+        response.num_cells =
+            self.schedule
+                .count_cells(request.metadata, sender, request.cell_options.mirrored())
+                as u16;
+        response
+    }
+
+    /// RFC 8480, Section 3.3.7: the payload of SIGNAL messages is opaque to 6P; hand it to the SF
+    /// and send back whatever it answers.
+    fn handle_signal_request(
+        &mut self,
+        sender: NeighborID,
+        request: &Request,
+        header: ResponseHdr,
+    ) -> SignalResponse {
+        let mut response = SignalResponse::new();
+        response.header = header;
+
+        let payload = self
+            .with_scheduling_function(request.header.sfid, |scheduling_function, _| {
+                scheduling_function.handle_signal(sender, &request.payload)
+            });
+        match payload {
+            Some(payload) => {
+                response.payload = payload;
+                response.header.code = ReturnCode::RC_SUCCESS;
+            }
+            None => response.header.code = ReturnCode::RC_ERR,
+        }
+        response
+    }
+
+    /// RFC 8480, Section 3.3.6: remove all cells we have scheduled with `sender` and reset its
+    /// SeqNum, see remove_neighbor(). CLEAR requests are always answered with RC_SUCCESS and
+    /// abort any ongoing transaction with `sender`; if we started it, its SF learns so through
+    /// TransactionOutcome::Cleared.
+    fn handle_clear_request(
+        &mut self,
+        sender: NeighborID,
+        request: &Request,
+        response: &mut Response,
+    ) {
+        // starting over with SeqNum 0 is the same as not knowing `sender` at all
+        self.remove_neighbor(sender);
+
+        response.header.code = ReturnCode::RC_SUCCESS;
+        response.header.seqnum = request.header.seqnum;
+    }
+
+    /// RFC 8480, Section 3.3.5: return up to MaxNumCells of the cells we have scheduled with
+    /// `sender`, starting at Offset. RC_EOL signals that there are no cells beyond this page.
+    fn handle_list_request(&self, sender: NeighborID, request: &Request, response: &mut Response) {
+        let cell_list =
+            self.schedule
+                .list_cells(request.metadata, sender, request.cell_options.mirrored());
+        let offset = request.offset as usize;
+        // pages can't be longer than what fits into a response
+        let max_num_cells = (request.max_num_cells as usize).min(MAX_NUM_CELLS);
+        let end = offset.saturating_add(max_num_cells);
+
+        if let Some(page) = cell_list.get(offset..end.min(cell_list.len())) {
+            response.cell_list.extend_from_slice(page).ok();
+        }
+
+        response.header.code = if end >= cell_list.len() {
+            ReturnCode::RC_EOL
+        } else {
+            ReturnCode::RC_SUCCESS
+        };
+    }
+
+    /// RFC 8480, Section 3.3.3: all cells in the RelocationCellList have to be scheduled with
+    /// `sender` using the requested cell options, mirrored to our point of view. The chosen
+    /// candidates replace the cells of the RelocationCellList in order; if we find fewer than
+    /// NumCells free candidates, only the first cells of the RelocationCellList are relocated.
+    fn handle_relocate_request(
+        &mut self,
+        sender: NeighborID,
+        request: &Request,
+        response: &mut Response,
+    ) {
+        if !self.is_relocatable(sender, request) {
+            response.header.code = ReturnCode::RC_ERR_CELLLIST;
+            return;
+        }
+
+        if self.is_relocation_locked(request) {
+            response.header.code = ReturnCode::RC_ERR_LOCKED;
+            return;
+        }
+
+        self.pick_candidates(sender, request, response);
+    }
+
+    /// Check whether the RelocationCellList of `request` only contains cells we have scheduled
+    /// with `sender` using the requested cell options, mirrored to our point of view.
+    fn is_relocatable(&self, sender: NeighborID, request: &Request) -> bool {
+        match &request.relocation_cell_list {
+            Some(relocation_cell_list) => {
+                relocation_cell_list.len() == request.num_cells as usize
+                    && relocation_cell_list.iter().all(|cell| {
+                        self.schedule.is_scheduled_with(
+                            request.metadata,
+                            cell,
+                            sender,
+                            request.cell_options.mirrored(),
+                        )
+                    })
+            }
+            None => false,
         }
     }
+
+    /// Check whether another transaction has locked a cell of the RelocationCellList of
+    /// `request`.
+    fn is_relocation_locked(&self, request: &Request) -> bool {
+        request
+            .relocation_cell_list
+            .iter()
+            .flatten()
+            .any(|cell| self.schedule.is_locked(request.metadata, cell))
+    }
 }
 
-#[cfg(test)]
+/// The body of a response, which depends on the type of the request it answers.
+#[derive(Debug, Copy, Clone)]
+enum ResponseBody<'a> {
+    CellList(&'a [Cell]),
+    NumCells(u16),
+    Payload(&'a [u8]),
+}
+
+impl<'a> ResponseBody<'a> {
+    /// Check that the body can belong to a response to a request of type `request_type`.
+    fn expect(self, request_type: RequestType) -> Result<ResponseBody<'a>, SixtopError> {
+        match (request_type, self) {
+            (RequestType::COUNT, ResponseBody::NumCells(_))
+            | (RequestType::SIGNAl, ResponseBody::Payload(_)) => Ok(self),
+            // parsed without knowing the request, a response without a body looks like one
+            // with an empty CellList. Error responses to COUNT requests may come without
+            // NumCells.
+            (RequestType::COUNT, ResponseBody::CellList([])) => Ok(ResponseBody::NumCells(0)),
+            (RequestType::SIGNAl, ResponseBody::CellList([])) => Ok(ResponseBody::Payload(&[])),
+            (RequestType::COUNT, _)
+            | (RequestType::SIGNAl, _)
+            | (_, ResponseBody::NumCells(_))
+            | (_, ResponseBody::Payload(_)) => Err(SixtopError::InvalidResponse),
+            _ => Ok(self),
+        }
+    }
+}
+
+fn to_cell_list(cells: &[Cell]) -> Result<CellList, SixtopError> {
+    CellList::from_slice(cells).map_err(|_| SixtopError::CapacityExceeded)
+}
+
+pub(crate) fn new_request(code: RequestType, sfid: SFID, metadata: u16) -> Request {
+    let mut request = Request::new();
+    request.header.code = code;
+    request.header.sfid = sfid;
+    request.metadata = metadata;
+    request
+}
+
+/// Turn `request` into an ADD or DELETE request for `num_cells` of the `candidates`.
+pub(crate) fn cell_request(
+    mut request: Request,
+    cell_options: CellOptions,
+    num_cells: u8,
+    candidates: &[Cell],
+) -> Result<Request, SixtopError> {
+    request.cell_options = cell_options;
+    request.num_cells = num_cells;
+    request.cell_list = to_cell_list(candidates)?;
+    Ok(request)
+}
+
+/// Turn `request` into a RELOCATE request that moves `relocation_cell_list` to some of the
+/// `candidates`.
+pub(crate) fn relocate_request(
+    mut request: Request,
+    cell_options: CellOptions,
+    relocation_cell_list: &[Cell],
+    candidates: &[Cell],
+) -> Result<Request, SixtopError> {
+    request.cell_options = cell_options;
+    request.num_cells = relocation_cell_list.len() as u8;
+    request.relocation_cell_list = Some(to_cell_list(relocation_cell_list)?);
+    request.cell_list = to_cell_list(candidates)?;
+    Ok(request)
+}
+
+/// Turn `request` into a COUNT request for the cells with `cell_options`.
+pub(crate) fn count_request(mut request: Request, cell_options: CellOptions) -> Request {
+    request.cell_options = cell_options;
+    request
+}
+
+/// Turn `request` into a LIST request for up to `max_num_cells` of the cells with
+/// `cell_options`, starting at `offset`.
+pub(crate) fn list_request(
+    mut request: Request,
+    cell_options: CellOptions,
+    offset: u16,
+    max_num_cells: u16,
+) -> Request {
+    request.cell_options = cell_options;
+    request.offset = offset;
+    request.max_num_cells = max_num_cells;
+    request
+}
+
+/// Turn `request` into a SIGNAL request carrying `payload`.
+pub(crate) fn signal_request(mut request: Request, payload: &[u8]) -> Result<Request, SixtopError> {
+    request.payload = Payload::from_slice(payload).map_err(|_| SixtopError::CapacityExceeded)?;
+    Ok(request)
+}
+
+/// RFC 8480, Section 3.4.5: the answer to a request of a version we don't speak. It has to be a
+/// version 0 response, which all versions understand.
+fn version_error_response(request_header: &MsgHdr) -> SixtopMsg {
+    let mut response = Response::new();
+    response.header.code = ReturnCode::RC_ERR_VERSION;
+    response.header.sfid = request_header.sfid;
+    response.header.seqnum = request_header.seqnum;
+    SixtopMsg::ResponseMsg(response)
+}
+
+/// The cells a transaction started by `request` locks: `cell_list` plus the RelocationCellList.
+fn cells_to_lock(
+    request: &Request,
+    cell_list: &CellList,
+) -> heapless::Vec<Cell, { 2 * MAX_NUM_CELLS }> {
+    cell_list
+        .iter()
+        .chain(request.relocation_cell_list.iter().flatten())
+        .copied()
+        .collect()
+}
+
+#[cfg(all(test, feature = "std"))]
 mod tests {
+    use super::*;
+    use crate::msg_builder::{serialize_message, MAX_MSG_SZ_BYTES};
+    use crate::msg_reader::deserialize_message;
+    use crate::schedule::SlotframeID;
+    use crate::types::{SchedulingFunction, DEFAULT_SFID, SFID};
+    use core::convert::TryInto;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    const TEST_NEIGHBOR: NeighborID = NeighborID::Short(22);
+    const TEST_OTHER_NEIGHBOR: NeighborID = NeighborID::Short(23);
+    const TEST_CELL_OPTIONS: CellOptions = CellOptions::TX;
+    /// TEST_CELL_OPTIONS as seen by the node that receives the request
+    const TEST_MIRRORED_CELL_OPTIONS: CellOptions = CellOptions::RX;
+    const TEST_SLOTFRAME: SlotframeID = 0;
+
+    fn test_cells() -> CellList {
+        CellList::from_slice(&[
+            Cell {
+                slot_offset: 1,
+                channel_offset: 2,
+            },
+            Cell {
+                slot_offset: 3,
+                channel_offset: 9,
+            },
+        ])
+        .unwrap()
+    }
+
+    fn build_request(code: RequestType, num_cells: u8, cell_list: CellList) -> SixtopMsg {
+        let mut request = Request::new();
+        request.header.code = code;
+        request.cell_options = TEST_CELL_OPTIONS;
+        request.num_cells = num_cells;
+        request.cell_list = cell_list;
+        SixtopMsg::RequestMsg(request)
+    }
+
+    fn unwrap_response(msg: Option<SixtopMsg>) -> Response {
+        match msg {
+            Some(SixtopMsg::ResponseMsg(response)) => response,
+            _ => panic!("expected a response"),
+        }
+    }
+
     #[test]
     fn test_lib() {
         assert_eq!(0, 0);
     }
+
+    #[test]
+    fn test_handle_add_request() {
+        let mut sixtop = Sixtop::new();
+
+        // RUN TEST
+        let result = sixtop
+            .handle_msg(
+                TEST_NEIGHBOR,
+                build_request(RequestType::ADD, 1, test_cells()),
+            )
+            .unwrap();
+
+        sixtop.on_link_ack(TEST_NEIGHBOR, 0).unwrap();
+
+        // ASSERT POSTCONDITION
+        let response = unwrap_response(result);
+        assert_eq!(response.header.code, ReturnCode::RC_SUCCESS);
+        assert_eq!(response.cell_list, [test_cells()[0]]);
+        assert!(sixtop.schedule().is_scheduled_with(
+            TEST_SLOTFRAME,
+            &test_cells()[0],
+            TEST_NEIGHBOR,
+            TEST_MIRRORED_CELL_OPTIONS
+        ));
+        assert!(!sixtop
+            .schedule()
+            .is_scheduled(TEST_SLOTFRAME, &test_cells()[1]));
+    }
+
+    #[test]
+    fn test_handle_delete_request() {
+        let mut sixtop = Sixtop::new();
+        for cell in test_cells() {
+            sixtop
+                .schedule
+                .add_cell(
+                    TEST_SLOTFRAME,
+                    cell,
+                    TEST_NEIGHBOR,
+                    TEST_MIRRORED_CELL_OPTIONS,
+                )
+                .unwrap();
+        }
+
+        // RUN TEST
+        let result = sixtop
+            .handle_msg(
+                TEST_NEIGHBOR,
+                build_request(RequestType::DELETE, 1, test_cells()),
+            )
+            .unwrap();
+
+        sixtop.on_link_ack(TEST_NEIGHBOR, 0).unwrap();
+
+        // ASSERT POSTCONDITION
+        let response = unwrap_response(result);
+        assert_eq!(response.header.code, ReturnCode::RC_SUCCESS);
+        assert_eq!(response.cell_list, [test_cells()[0]]);
+        assert!(!sixtop
+            .schedule()
+            .is_scheduled(TEST_SLOTFRAME, &test_cells()[0]));
+        assert!(sixtop
+            .schedule()
+            .is_scheduled(TEST_SLOTFRAME, &test_cells()[1]));
+    }
+
+    #[test]
+    fn test_handle_add_request_link_nack() {
+        let mut sixtop = Sixtop::new();
+        let result = sixtop
+            .handle_msg(
+                TEST_NEIGHBOR,
+                build_request(RequestType::ADD, 1, test_cells()),
+            )
+            .unwrap();
+        assert_eq!(
+            sixtop.transaction_state(TEST_NEIGHBOR),
+            TransactionState::WaitingForLinkAck
+        );
+
+        // RUN TEST
+        sixtop.on_link_nack(TEST_NEIGHBOR, 0).unwrap();
+
+        // ASSERT POSTCONDITION
+        assert_eq!(unwrap_response(result).cell_list, [test_cells()[0]]);
+        assert!(sixtop.schedule().is_empty());
+        assert_eq!(
+            sixtop.transaction_state(TEST_NEIGHBOR),
+            TransactionState::Idle
+        );
+        // we did see the request, so the SeqNum moves on anyway
+        assert_eq!(*sixtop.seqnums.get_seqnum(TEST_NEIGHBOR).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_on_link_ack_wrong_seqnum() {
+        let mut sixtop = Sixtop::new();
+        sixtop
+            .handle_msg(
+                TEST_NEIGHBOR,
+                build_request(RequestType::ADD, 1, test_cells()),
+            )
+            .unwrap();
+
+        // RUN TEST
+        let result = sixtop.on_link_ack(TEST_NEIGHBOR, 3);
+
+        // ASSERT POSTCONDITION
+        assert!(result.is_err());
+        assert!(sixtop.schedule().is_empty());
+    }
+
+    #[test]
+    fn test_handle_delete_request_unknown_cell() {
+        let mut sixtop = Sixtop::new();
+        sixtop
+            .schedule
+            .add_cell(
+                TEST_SLOTFRAME,
+                test_cells()[0],
+                TEST_NEIGHBOR,
+                TEST_MIRRORED_CELL_OPTIONS,
+            )
+            .unwrap();
+
+        // RUN TEST
+        let result = sixtop
+            .handle_msg(
+                TEST_NEIGHBOR,
+                build_request(RequestType::DELETE, 1, test_cells()),
+            )
+            .unwrap();
+
+        // ASSERT POSTCONDITION
+        let response = unwrap_response(result);
+        assert_eq!(response.header.code, ReturnCode::RC_ERR_CELLLIST);
+        assert!(response.cell_list.is_empty());
+        assert!(sixtop
+            .schedule()
+            .is_scheduled(TEST_SLOTFRAME, &test_cells()[0]));
+    }
+
+    fn build_relocate_request(relocation_cell_list: &[Cell], cell_list: &[Cell]) -> SixtopMsg {
+        let mut request = Request::new();
+        request.header.code = RequestType::RELOCATE;
+        request.cell_options = TEST_CELL_OPTIONS;
+        request.num_cells = relocation_cell_list.len() as u8;
+        request.relocation_cell_list = Some(CellList::from_slice(relocation_cell_list).unwrap());
+        request.cell_list = CellList::from_slice(cell_list).unwrap();
+        SixtopMsg::RequestMsg(request)
+    }
+
+    #[test]
+    fn test_handle_relocate_request() {
+        let mut sixtop = Sixtop::new();
+        let old_cell = Cell {
+            slot_offset: 7,
+            channel_offset: 1,
+        };
+        sixtop
+            .schedule
+            .add_cell(
+                TEST_SLOTFRAME,
+                old_cell,
+                TEST_NEIGHBOR,
+                TEST_MIRRORED_CELL_OPTIONS,
+            )
+            .unwrap();
+        // the first candidate is taken already
+        sixtop
+            .schedule
+            .add_cell(
+                TEST_SLOTFRAME,
+                test_cells()[0],
+                TEST_OTHER_NEIGHBOR,
+                TEST_MIRRORED_CELL_OPTIONS,
+            )
+            .unwrap();
+
+        // RUN TEST
+        let result = sixtop
+            .handle_msg(
+                TEST_NEIGHBOR,
+                build_relocate_request(&[old_cell], &test_cells()),
+            )
+            .unwrap();
+
+        sixtop.on_link_ack(TEST_NEIGHBOR, 0).unwrap();
+
+        // ASSERT POSTCONDITION
+        let response = unwrap_response(result);
+        assert_eq!(response.header.code, ReturnCode::RC_SUCCESS);
+        assert_eq!(response.cell_list, [test_cells()[1]]);
+        assert!(!sixtop.schedule().is_scheduled(TEST_SLOTFRAME, &old_cell));
+        assert!(sixtop.schedule().is_scheduled_with(
+            TEST_SLOTFRAME,
+            &test_cells()[1],
+            TEST_NEIGHBOR,
+            TEST_MIRRORED_CELL_OPTIONS
+        ));
+    }
+
+    #[test]
+    fn test_handle_relocate_request_no_free_candidate() {
+        let mut sixtop = Sixtop::new();
+        let old_cell = Cell {
+            slot_offset: 7,
+            channel_offset: 1,
+        };
+        sixtop
+            .schedule
+            .add_cell(
+                TEST_SLOTFRAME,
+                old_cell,
+                TEST_NEIGHBOR,
+                TEST_MIRRORED_CELL_OPTIONS,
+            )
+            .unwrap();
+
+        // RUN TEST
+        let result = sixtop
+            .handle_msg(
+                TEST_NEIGHBOR,
+                build_relocate_request(&[old_cell], &[old_cell]),
+            )
+            .unwrap();
+
+        // ASSERT POSTCONDITION
+        let response = unwrap_response(result);
+        assert_eq!(response.header.code, ReturnCode::RC_SUCCESS);
+        assert!(response.cell_list.is_empty());
+        assert!(sixtop.schedule().is_scheduled(TEST_SLOTFRAME, &old_cell));
+    }
+
+    #[test]
+    fn test_handle_relocate_request_unknown_cell() {
+        let mut sixtop = Sixtop::new();
+        let old_cell = Cell {
+            slot_offset: 7,
+            channel_offset: 1,
+        };
+
+        // RUN TEST
+        let result = sixtop
+            .handle_msg(
+                TEST_NEIGHBOR,
+                build_relocate_request(&[old_cell], &test_cells()),
+            )
+            .unwrap();
+
+        // ASSERT POSTCONDITION
+        let response = unwrap_response(result);
+        assert_eq!(response.header.code, ReturnCode::RC_ERR_CELLLIST);
+        assert!(sixtop.schedule().is_empty());
+    }
+
+    #[test]
+    fn test_handle_count_request() {
+        let mut sixtop = Sixtop::new();
+        for cell in test_cells() {
+            sixtop
+                .schedule
+                .add_cell(
+                    TEST_SLOTFRAME,
+                    cell,
+                    TEST_NEIGHBOR,
+                    TEST_MIRRORED_CELL_OPTIONS,
+                )
+                .unwrap();
+        }
+        // neither shared with the sender nor using the requested options as seen from our side:
+        // mustn't be counted
+        sixtop
+            .schedule
+            .add_cell(
+                TEST_SLOTFRAME,
+                Cell {
+                    slot_offset: 20,
+                    channel_offset: 0,
+                },
+                TEST_OTHER_NEIGHBOR,
+                TEST_MIRRORED_CELL_OPTIONS,
+            )
+            .unwrap();
+        sixtop
+            .schedule
+            .add_cell(
+                TEST_SLOTFRAME,
+                Cell {
+                    slot_offset: 21,
+                    channel_offset: 0,
+                },
+                TEST_NEIGHBOR,
+                CellOptions::TX,
+            )
+            .unwrap();
+
+        // RUN TEST
+        let result = sixtop
+            .handle_msg(
+                TEST_NEIGHBOR,
+                build_request(RequestType::COUNT, 0, CellList::new()),
+            )
+            .unwrap();
+
+        // ASSERT POSTCONDITION
+        if let Some(SixtopMsg::CountResponseMsg(response)) = result {
+            assert_eq!(response.header.code, ReturnCode::RC_SUCCESS);
+            assert_eq!(response.num_cells, 2);
+        } else {
+            panic!("expected a count response");
+        }
+    }
+
+    #[test]
+    fn test_handle_count_request_without_cell_options() {
+        let mut sixtop = Sixtop::new();
+        for (cell, cell_options) in test_cells()
+            .iter()
+            .zip([CellOptions::TX, CellOptions::RX | CellOptions::SHARED].iter())
+        {
+            sixtop
+                .schedule
+                .add_cell(TEST_SLOTFRAME, *cell, TEST_NEIGHBOR, *cell_options)
+                .unwrap();
+        }
+
+        // RUN TEST
+        let mut request = Request::new();
+        request.header.code = RequestType::COUNT;
+        let result = sixtop
+            .handle_msg(TEST_NEIGHBOR, SixtopMsg::RequestMsg(request))
+            .unwrap();
+
+        // ASSERT POSTCONDITION
+        if let Some(SixtopMsg::CountResponseMsg(response)) = result {
+            assert_eq!(response.num_cells, 2);
+        } else {
+            panic!("expected a count response");
+        }
+    }
+
+    #[test]
+    fn test_handle_unassigned_request() {
+        let mut sixtop = Sixtop::new();
+
+        // RUN TEST
+        let mut request = Request::new();
+        request.header.code = RequestType::Unassigned(42);
+        let result = sixtop
+            .handle_msg(TEST_NEIGHBOR, SixtopMsg::RequestMsg(request))
+            .unwrap();
+
+        // ASSERT POSTCONDITION
+        if let Some(SixtopMsg::ResponseMsg(response)) = result {
+            assert_eq!(response.header.code, ReturnCode::RC_ERR);
+        } else {
+            panic!("expected a response");
+        }
+    }
+
+    #[test]
+    fn test_handle_request_unsupported_version() {
+        let mut sixtop = Sixtop::new();
+        // an ADD request of version 1 whose body we can't read
+        let test_msg = vec![0b0001_0000, RequestType::ADD.to_u8(), 42, 7, 0xFF];
+
+        // RUN TEST
+        let request = deserialize_message(test_msg).unwrap();
+        let result = sixtop.handle_msg(TEST_NEIGHBOR, request).unwrap();
+
+        // ASSERT POSTCONDITION
+        if let Some(SixtopMsg::ResponseMsg(response)) = result {
+            assert_eq!(response.header.version, SIXTOP_VERSION);
+            assert_eq!(response.header.code, ReturnCode::RC_ERR_VERSION);
+            assert_eq!(response.header.sfid, 42);
+            assert_eq!(response.header.seqnum, 7);
+        } else {
+            panic!("expected a response");
+        }
+        assert!(!sixtop.transactions.contains_key(&TEST_NEIGHBOR));
+    }
+
+    #[test]
+    fn test_handle_response_unsupported_version() {
+        let mut sixtop = Sixtop::new();
+        let mut response = Response::new();
+        response.header.version = 1;
+
+        // RUN TEST
+        let result = sixtop.handle_msg(TEST_NEIGHBOR, SixtopMsg::ResponseMsg(response));
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result.err(), Some(SixtopError::UnsupportedVersion(1)));
+    }
+
+    #[test]
+    fn test_handle_response_version_error() {
+        let mut initiator = Sixtop::new();
+        initiator
+            .count_cells(
+                TEST_RESPONDER,
+                TEST_CELL_OPTIONS,
+                &mut [0; MAX_MSG_SZ_BYTES],
+            )
+            .unwrap();
+        initiator.on_link_ack(TEST_RESPONDER, 0).unwrap();
+        let request = SixtopMsg::RequestMsg(Request::new());
+
+        // RUN TEST
+        // the responder answers like we do when the version doesn't match
+        let response = version_error_response(&request.header());
+        let result = initiator.handle_msg(TEST_RESPONDER, response).unwrap();
+
+        // ASSERT POSTCONDITION
+        assert!(result.is_none());
+        assert!(!initiator.transactions.contains_key(&TEST_RESPONDER));
+        // the responder didn't process the request, so the SeqNum stays where it was
+        assert_eq!(*initiator.seqnums.get_seqnum(TEST_RESPONDER).unwrap(), 0);
+    }
+
+    fn build_list_request(offset: u16, max_num_cells: u16) -> SixtopMsg {
+        let mut request = Request::new();
+        request.header.code = RequestType::LIST;
+        request.cell_options = TEST_CELL_OPTIONS;
+        request.offset = offset;
+        request.max_num_cells = max_num_cells;
+        SixtopMsg::RequestMsg(request)
+    }
+
+    #[test]
+    fn test_handle_list_request() {
+        let mut sixtop = Sixtop::new();
+        for cell in test_cells() {
+            sixtop
+                .schedule
+                .add_cell(
+                    TEST_SLOTFRAME,
+                    cell,
+                    TEST_NEIGHBOR,
+                    TEST_MIRRORED_CELL_OPTIONS,
+                )
+                .unwrap();
+        }
+
+        // RUN TEST
+        let result = sixtop
+            .handle_msg(TEST_NEIGHBOR, build_list_request(0, 1))
+            .unwrap();
+
+        // ASSERT POSTCONDITION
+        let response = unwrap_response(result);
+        assert_eq!(response.header.code, ReturnCode::RC_SUCCESS);
+        assert_eq!(response.cell_list, [test_cells()[0]]);
+    }
+
+    #[test]
+    fn test_handle_list_request_eol() {
+        let mut sixtop = Sixtop::new();
+        for cell in test_cells() {
+            sixtop
+                .schedule
+                .add_cell(
+                    TEST_SLOTFRAME,
+                    cell,
+                    TEST_NEIGHBOR,
+                    TEST_MIRRORED_CELL_OPTIONS,
+                )
+                .unwrap();
+        }
+
+        // RUN TEST
+        let result = sixtop
+            .handle_msg(TEST_NEIGHBOR, build_list_request(1, 5))
+            .unwrap();
+
+        // ASSERT POSTCONDITION
+        let response = unwrap_response(result);
+        assert_eq!(response.header.code, ReturnCode::RC_EOL);
+        assert_eq!(response.cell_list, [test_cells()[1]]);
+    }
+
+    #[test]
+    fn test_handle_clear_request() {
+        let mut sixtop = Sixtop::new();
+        let other_cell = Cell {
+            slot_offset: 20,
+            channel_offset: 0,
+        };
+        for cell in test_cells() {
+            sixtop
+                .schedule
+                .add_cell(
+                    TEST_SLOTFRAME,
+                    cell,
+                    TEST_NEIGHBOR,
+                    TEST_MIRRORED_CELL_OPTIONS,
+                )
+                .unwrap();
+        }
+        sixtop
+            .schedule
+            .add_cell(
+                TEST_SLOTFRAME,
+                other_cell,
+                TEST_OTHER_NEIGHBOR,
+                TEST_MIRRORED_CELL_OPTIONS,
+            )
+            .unwrap();
+        sixtop.seqnums.add_neighbor(TEST_NEIGHBOR, 5).unwrap();
+
+        // RUN TEST
+        let mut request = Request::new();
+        request.header.code = RequestType::CLEAR;
+        request.header.seqnum = 9;
+        let result = sixtop
+            .handle_msg(TEST_NEIGHBOR, SixtopMsg::RequestMsg(request))
+            .unwrap();
+
+        // ASSERT POSTCONDITION
+        let response = unwrap_response(result);
+        assert_eq!(response.header.code, ReturnCode::RC_SUCCESS);
+        assert_eq!(response.header.seqnum, 9);
+        assert_eq!(sixtop.schedule().len(), 1);
+        assert!(sixtop.schedule().is_scheduled(TEST_SLOTFRAME, &other_cell));
+        assert_eq!(
+            sixtop.seqnums.guaranteed_get_seqnum(TEST_NEIGHBOR),
+            Ok(START_SEQNUM)
+        );
+    }
+
+    struct EchoSF {
+        sfid: SFID,
+    }
+
+    impl SchedulingFunction for EchoSF {
+        fn sfid(&self) -> SFID {
+            self.sfid
+        }
+
+        fn handle_signal(&mut self, neighbor: NeighborID, payload: &[u8]) -> Payload {
+            let mut answer = Payload::new();
+            if let NeighborID::Short(addr) = neighbor {
+                answer.extend_from_slice(&addr.to_be_bytes()).unwrap();
+            }
+            answer.extend_from_slice(payload).unwrap();
+            answer
+        }
+    }
+
+    #[test]
+    fn test_handle_signal_request() {
+        let mut sixtop = Sixtop::new();
+        sixtop
+            .add_scheduling_function(Box::new(EchoSF { sfid: DEFAULT_SFID }))
+            .unwrap();
+
+        // RUN TEST
+        let mut request = Request::new();
+        request.header.code = RequestType::SIGNAl;
+        request.payload = Payload::from_slice(&[0xCA, 0xFE]).unwrap();
+        let result = sixtop
+            .handle_msg(TEST_NEIGHBOR, SixtopMsg::RequestMsg(request))
+            .unwrap();
+
+        // ASSERT POSTCONDITION
+        if let Some(SixtopMsg::SignalResponseMsg(response)) = result {
+            assert_eq!(response.header.code, ReturnCode::RC_SUCCESS);
+            assert_eq!(response.payload, [0, 22, 0xCA, 0xFE]);
+        } else {
+            panic!("expected a signal response");
+        }
+    }
+
+    #[test]
+    fn test_handle_signal_request_without_sf() {
+        let mut sixtop = Sixtop::new();
+
+        // RUN TEST
+        let mut request = Request::new();
+        request.header.code = RequestType::SIGNAl;
+        request.payload = Payload::from_slice(&[0xCA, 0xFE]).unwrap();
+        let result = sixtop
+            .handle_msg(TEST_NEIGHBOR, SixtopMsg::RequestMsg(request))
+            .unwrap();
+
+        // ASSERT POSTCONDITION
+        if let Some(SixtopMsg::SignalResponseMsg(response)) = result {
+            assert_eq!(response.header.code, ReturnCode::RC_ERR);
+            assert!(response.payload.is_empty());
+        } else {
+            panic!("expected a signal response");
+        }
+    }
+
+    /// SFID of an experimental SF that runs next to the default one
+    const TEST_OTHER_SFID: SFID = 0x42;
+
+    #[test]
+    fn test_handle_signal_request_per_sfid() {
+        let mut sixtop = Sixtop::new();
+        sixtop
+            .add_scheduling_function(Box::new(EchoSF {
+                sfid: TEST_OTHER_SFID,
+            }))
+            .unwrap();
+
+        // RUN TEST
+        let mut request = Request::new();
+        request.header.code = RequestType::SIGNAl;
+        request.header.sfid = TEST_OTHER_SFID;
+        request.payload = Payload::from_slice(&[0xCA, 0xFE]).unwrap();
+        let other_result = sixtop
+            .handle_msg(TEST_NEIGHBOR, SixtopMsg::RequestMsg(request.clone()))
+            .unwrap();
+        // SeqNums are per neighbor, not per SF
+        request.header.sfid = DEFAULT_SFID;
+        request.header.seqnum = 1;
+        let default_result = sixtop
+            .handle_msg(TEST_NEIGHBOR, SixtopMsg::RequestMsg(request))
+            .unwrap();
+
+        // ASSERT POSTCONDITION
+        if let Some(SixtopMsg::SignalResponseMsg(response)) = other_result {
+            assert_eq!(response.header.code, ReturnCode::RC_SUCCESS);
+            assert_eq!(response.header.sfid, TEST_OTHER_SFID);
+            assert_eq!(response.payload, [0, 22, 0xCA, 0xFE]);
+        } else {
+            panic!("expected a signal response");
+        }
+        // there's no SF for our own SFID
+        if let Some(SixtopMsg::SignalResponseMsg(response)) = default_result {
+            assert_eq!(response.header.code, ReturnCode::RC_ERR);
+            assert_eq!(response.header.sfid, DEFAULT_SFID);
+        } else {
+            panic!("expected a signal response");
+        }
+    }
+
+    #[test]
+    fn test_handle_request_unknown_sfid() {
+        let mut sixtop = Sixtop::new();
+
+        // RUN TEST
+        let mut request = Request::new();
+        request.header.code = RequestType::COUNT;
+        request.header.sfid = TEST_OTHER_SFID;
+        request.header.seqnum = 7;
+        let result = sixtop
+            .handle_msg(TEST_NEIGHBOR, SixtopMsg::RequestMsg(request))
+            .unwrap();
+
+        // ASSERT POSTCONDITION
+        let response = unwrap_response(result);
+        assert_eq!(response.header.code, ReturnCode::RC_ERR_SFID);
+        assert_eq!(response.header.sfid, TEST_OTHER_SFID);
+        assert_eq!(response.header.seqnum, 7);
+        assert!(sixtop.seqnums.get_seqnum(TEST_NEIGHBOR).is_none());
+    }
+
+    #[test]
+    fn test_transaction_after_unknown_sfid() {
+        let mut initiator = Sixtop::new();
+        initiator
+            .add_scheduling_function(Box::new(EchoSF {
+                sfid: TEST_OTHER_SFID,
+            }))
+            .unwrap();
+        let mut responder = Sixtop::new();
+        let mut count = |sfid: SFID| {
+            let request = count_request(
+                new_request(RequestType::COUNT, sfid, TEST_SLOTFRAME),
+                TEST_CELL_OPTIONS,
+            );
+            let mut buf = [0; MAX_MSG_SZ_BYTES];
+            let len = initiator
+                .start_transaction(TEST_RESPONDER, request, &mut buf)
+                .unwrap();
+            let request = deserialize_message(buf[..len].to_vec()).unwrap();
+            let seqnum = initiator.transaction_seqnum(TEST_RESPONDER).unwrap();
+            initiator.on_link_ack(TEST_RESPONDER, seqnum).unwrap();
+            let response = responder
+                .handle_msg(TEST_NEIGHBOR, request)
+                .unwrap()
+                .unwrap();
+            let code = response.header().code;
+            initiator.handle_msg(TEST_RESPONDER, response).unwrap();
+            ReturnCode::from_wire(code)
+        };
+
+        // RUN TEST
+        let first = count(DEFAULT_SFID);
+        let unknown = count(TEST_OTHER_SFID);
+        let after_unknown = count(DEFAULT_SFID);
+
+        // ASSERT POSTCONDITION
+        assert_eq!(first, ReturnCode::RC_SUCCESS);
+        assert_eq!(unknown, ReturnCode::RC_ERR_SFID);
+        assert_eq!(after_unknown, ReturnCode::RC_SUCCESS);
+        assert_eq!(*initiator.seqnums.get_seqnum(TEST_RESPONDER).unwrap(), 2);
+        assert_eq!(*responder.seqnums.get_seqnum(TEST_NEIGHBOR).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_start_transaction_unknown_sfid() {
+        let mut initiator = Sixtop::new();
+        let mut request = Request::new();
+        request.header.sfid = TEST_OTHER_SFID;
+
+        // RUN TEST
+        let result =
+            initiator.start_transaction(TEST_NEIGHBOR, request, &mut [0; MAX_MSG_SZ_BYTES]);
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result, Err(SixtopError::BadSfid(TEST_OTHER_SFID)));
+        assert_eq!(
+            initiator.transaction_state(TEST_NEIGHBOR),
+            TransactionState::Idle
+        );
+    }
+
+    #[test]
+    fn test_add_scheduling_function_capacity() {
+        let mut sixtop = Sixtop::new();
+        for sfid in 0..MAX_SCHEDULING_FUNCTIONS as SFID {
+            sixtop
+                .add_scheduling_function(Box::new(EchoSF { sfid }))
+                .unwrap();
+        }
+
+        // RUN TEST
+        let replaced = sixtop.add_scheduling_function(Box::new(EchoSF { sfid: 0 }));
+        let added = sixtop.add_scheduling_function(Box::new(EchoSF {
+            sfid: TEST_OTHER_SFID,
+        }));
+
+        // ASSERT POSTCONDITION
+        assert_eq!(replaced, Ok(()));
+        assert_eq!(added, Err(SixtopError::CapacityExceeded));
+    }
+
+    const TEST_RESPONDER: NeighborID = NeighborID::Short(33);
+
+    struct ProposingSF {}
+
+    impl SchedulingFunction for ProposingSF {
+        fn sfid(&self) -> SFID {
+            DEFAULT_SFID
+        }
+
+        fn propose_cells(&mut self, _neighbor: NeighborID, _request: &Request) -> CellList {
+            test_cells()
+        }
+    }
+
+    #[test]
+    fn test_three_step_add() {
+        let mut initiator = Sixtop::new();
+        let mut responder = Sixtop::new();
+        responder
+            .add_scheduling_function(Box::new(ProposingSF {}))
+            .unwrap();
+
+        let mut request = Request::new();
+        request.header.code = RequestType::ADD;
+        request.cell_options = TEST_CELL_OPTIONS;
+        request.num_cells = 1;
+
+        // RUN TEST
+        let mut buf = [0; MAX_MSG_SZ_BYTES];
+        let len = initiator
+            .start_transaction(TEST_RESPONDER, request, &mut buf)
+            .unwrap();
+        let response = responder
+            .handle_msg(
+                TEST_NEIGHBOR,
+                deserialize_message(buf[..len].to_vec()).unwrap(),
+            )
+            .unwrap()
+            .unwrap();
+        let confirmation = initiator
+            .handle_msg(TEST_RESPONDER, response)
+            .unwrap()
+            .unwrap();
+        initiator.on_link_ack(TEST_RESPONDER, 0).unwrap();
+        let data = serialize_message(confirmation).unwrap();
+        let result = responder
+            .handle_msg(TEST_NEIGHBOR, deserialize_message(data).unwrap())
+            .unwrap();
+
+        // ASSERT POSTCONDITION
+        assert!(result.is_none());
+        assert_eq!(initiator.schedule().len(), 1);
+        assert!(initiator.schedule().is_scheduled_with(
+            TEST_SLOTFRAME,
+            &test_cells()[0],
+            TEST_RESPONDER,
+            TEST_CELL_OPTIONS
+        ));
+        assert_eq!(responder.schedule().len(), 1);
+        assert!(responder.schedule().is_scheduled_with(
+            TEST_SLOTFRAME,
+            &test_cells()[0],
+            TEST_NEIGHBOR,
+            TEST_MIRRORED_CELL_OPTIONS
+        ));
+    }
+
+    #[test]
+    fn test_three_step_delete_response() {
+        let mut responder = Sixtop::new();
+        for cell in test_cells() {
+            responder
+                .schedule
+                .add_cell(
+                    TEST_SLOTFRAME,
+                    cell,
+                    TEST_NEIGHBOR,
+                    TEST_MIRRORED_CELL_OPTIONS,
+                )
+                .unwrap();
+        }
+
+        // RUN TEST
+        let result = responder
+            .handle_msg(
+                TEST_NEIGHBOR,
+                build_request(RequestType::DELETE, 1, CellList::new()),
+            )
+            .unwrap();
+
+        // ASSERT POSTCONDITION
+        let response = unwrap_response(result);
+        assert_eq!(response.header.code, ReturnCode::RC_SUCCESS);
+        assert_eq!(response.cell_list, test_cells());
+        // nothing is deleted before the confirmation arrives
+        assert_eq!(responder.schedule().len(), 2);
+    }
+
+    #[test]
+    fn test_three_step_confirmation_with_unproposed_cell() {
+        let mut responder = Sixtop::new();
+        responder
+            .add_scheduling_function(Box::new(ProposingSF {}))
+            .unwrap();
+        responder
+            .handle_msg(
+                TEST_NEIGHBOR,
+                build_request(RequestType::ADD, 1, CellList::new()),
+            )
+            .unwrap();
+
+        // RUN TEST
+        let mut confirmation = Confirmation::new();
+        confirmation.header.code = ReturnCode::RC_SUCCESS;
+        confirmation
+            .cell_list
+            .push(Cell {
+                slot_offset: 40,
+                channel_offset: 4,
+            })
+            .unwrap();
+        let result = responder.handle_msg(TEST_NEIGHBOR, SixtopMsg::ConfirmationMsg(confirmation));
+
+        // ASSERT POSTCONDITION
+        assert!(result.unwrap().is_none());
+        assert!(responder.schedule().is_empty());
+    }
+
+    #[test]
+    fn test_unexpected_confirmation() {
+        let mut responder = Sixtop::new();
+
+        // RUN TEST
+        let result = responder.handle_msg(
+            TEST_NEIGHBOR,
+            SixtopMsg::ConfirmationMsg(Confirmation::new()),
+        );
+
+        // ASSERT POSTCONDITION
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_two_step_add_initiator() {
+        let mut initiator = Sixtop::new();
+        let mut request = Request::new();
+        request.header.code = RequestType::ADD;
+        request.cell_options = TEST_CELL_OPTIONS;
+        request.num_cells = 1;
+        request.cell_list = test_cells();
+        initiator
+            .start_transaction(TEST_RESPONDER, request, &mut [0; MAX_MSG_SZ_BYTES])
+            .unwrap();
+
+        // RUN TEST
+        let mut response = Response::new();
+        response.header.code = ReturnCode::RC_SUCCESS;
+        response.cell_list.push(test_cells()[1]).unwrap();
+        let result = initiator
+            .handle_msg(TEST_RESPONDER, SixtopMsg::ResponseMsg(response))
+            .unwrap();
+
+        // ASSERT POSTCONDITION
+        assert!(result.is_none());
+        assert_eq!(initiator.schedule().len(), 1);
+        assert!(initiator.schedule().is_scheduled_with(
+            TEST_SLOTFRAME,
+            &test_cells()[1],
+            TEST_RESPONDER,
+            TEST_CELL_OPTIONS
+        ));
+    }
+
+    #[test]
+    fn test_add_cells() {
+        let mut initiator = Sixtop::new();
+        let mut responder = Sixtop::new();
+        initiator.set_metadata(7);
+
+        // RUN TEST
+        // two transactions in a row, the second one has to use the incremented SeqNum
+        for cell in test_cells() {
+            let mut buf = [0; MAX_MSG_SZ_BYTES];
+            let len = initiator
+                .add_cells(TEST_RESPONDER, TEST_CELL_OPTIONS, 1, &[cell], &mut buf)
+                .unwrap();
+            let request = deserialize_message(buf[..len].to_vec()).unwrap();
+            let seqnum = initiator.transaction_seqnum(TEST_RESPONDER).unwrap();
+            initiator.on_link_ack(TEST_RESPONDER, seqnum).unwrap();
+            if let SixtopMsg::RequestMsg(request) = &request {
+                assert_eq!(request.metadata, 7);
+            }
+            let response = responder
+                .handle_msg(TEST_NEIGHBOR, request)
+                .unwrap()
+                .unwrap();
+            responder.on_link_ack(TEST_NEIGHBOR, seqnum).unwrap();
+            initiator.handle_msg(TEST_RESPONDER, response).unwrap();
+        }
+
+        // ASSERT POSTCONDITION
+        assert_eq!(initiator.schedule().len(), 2);
+        assert_eq!(responder.schedule().len(), 2);
+        assert_eq!(*initiator.seqnums.get_seqnum(TEST_RESPONDER).unwrap(), 2);
+        assert_eq!(*responder.seqnums.get_seqnum(TEST_NEIGHBOR).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_response_wrong_seqnum() {
+        let mut initiator = Sixtop::new();
+        initiator
+            .add_cells(
+                TEST_RESPONDER,
+                TEST_CELL_OPTIONS,
+                1,
+                &test_cells(),
+                &mut [0; MAX_MSG_SZ_BYTES],
+            )
+            .unwrap();
+        let mut response = Response::new();
+        response.header.code = ReturnCode::RC_SUCCESS;
+        response.header.seqnum = 1;
+        response.cell_list.push(test_cells()[0]).unwrap();
+
+        // RUN TEST
+        let result = initiator.handle_msg(TEST_RESPONDER, SixtopMsg::ResponseMsg(response));
+
+        // ASSERT POSTCONDITION
+        assert!(result.is_err());
+        assert!(initiator.schedule().is_empty());
+        assert_eq!(
+            initiator.transaction_state(TEST_RESPONDER),
+            TransactionState::WaitingForResponse
+        );
+    }
+
+    #[test]
+    fn test_clear_initiator() {
+        let mut initiator = Sixtop::new();
+        initiator
+            .schedule
+            .add_cell(
+                TEST_SLOTFRAME,
+                test_cells()[0],
+                TEST_RESPONDER,
+                TEST_CELL_OPTIONS,
+            )
+            .unwrap();
+        initiator.seqnums.add_neighbor(TEST_RESPONDER, 5).unwrap();
+        initiator
+            .clear(TEST_RESPONDER, &mut [0; MAX_MSG_SZ_BYTES])
+            .unwrap();
+        let mut response = Response::new();
+        response.header.code = ReturnCode::RC_ERR;
+        response.header.seqnum = 5;
+
+        // RUN TEST
+        let result = initiator
+            .handle_msg(TEST_RESPONDER, SixtopMsg::ResponseMsg(response))
+            .unwrap();
+
+        // ASSERT POSTCONDITION
+        assert!(result.is_none());
+        assert!(initiator.schedule().is_empty());
+        assert_eq!(
+            initiator.seqnums.guaranteed_get_seqnum(TEST_RESPONDER),
+            Ok(START_SEQNUM)
+        );
+    }
+
+    #[test]
+    fn test_handle_add_request_locked() {
+        let mut sixtop = Sixtop::new();
+        sixtop
+            .add_cells(
+                TEST_RESPONDER,
+                TEST_CELL_OPTIONS,
+                2,
+                &test_cells(),
+                &mut [0; MAX_MSG_SZ_BYTES],
+            )
+            .unwrap();
+
+        // RUN TEST
+        let result = sixtop
+            .handle_msg(
+                TEST_NEIGHBOR,
+                build_request(RequestType::ADD, 1, test_cells()),
+            )
+            .unwrap();
+
+        // ASSERT POSTCONDITION
+        let response = unwrap_response(result);
+        assert_eq!(response.header.code, ReturnCode::RC_ERR_LOCKED);
+        assert_eq!(
+            sixtop.transaction_state(TEST_NEIGHBOR),
+            TransactionState::Idle
+        );
+    }
+
+    #[test]
+    fn test_handle_delete_request_locked() {
+        let mut sixtop = Sixtop::new();
+        for cell in test_cells() {
+            sixtop
+                .schedule
+                .add_cell(
+                    TEST_SLOTFRAME,
+                    cell,
+                    TEST_NEIGHBOR,
+                    TEST_MIRRORED_CELL_OPTIONS,
+                )
+                .unwrap();
+        }
+        sixtop
+            .schedule
+            .lock_cells(TEST_SLOTFRAME, TEST_RESPONDER, &test_cells()[1..])
+            .unwrap();
+
+        // RUN TEST
+        let result = sixtop
+            .handle_msg(
+                TEST_NEIGHBOR,
+                build_request(RequestType::DELETE, 2, test_cells()),
+            )
+            .unwrap();
+
+        // ASSERT POSTCONDITION
+        let response = unwrap_response(result);
+        assert_eq!(response.header.code, ReturnCode::RC_ERR_LOCKED);
+        assert_eq!(sixtop.schedule().len(), 2);
+    }
+
+    #[test]
+    fn test_link_nack_releases_locks() {
+        let mut initiator = Sixtop::new();
+        initiator
+            .add_cells(
+                TEST_RESPONDER,
+                TEST_CELL_OPTIONS,
+                1,
+                &test_cells(),
+                &mut [0; MAX_MSG_SZ_BYTES],
+            )
+            .unwrap();
+        assert!(initiator
+            .schedule()
+            .is_locked(TEST_SLOTFRAME, &test_cells()[0]));
+
+        // RUN TEST
+        initiator.on_link_nack(TEST_RESPONDER, 0).unwrap();
+
+        // ASSERT POSTCONDITION
+        for cell in test_cells() {
+            assert!(initiator.schedule().is_available(TEST_SLOTFRAME, &cell));
+        }
+    }
+
+    #[test]
+    fn test_start_transaction_busy() {
+        let mut initiator = Sixtop::new();
+        initiator
+            .start_transaction(TEST_RESPONDER, Request::new(), &mut [0; MAX_MSG_SZ_BYTES])
+            .unwrap();
+
+        // RUN TEST
+        let result =
+            initiator.start_transaction(TEST_RESPONDER, Request::new(), &mut [0; MAX_MSG_SZ_BYTES]);
+
+        // ASSERT POSTCONDITION
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_handle_request_busy() {
+        let mut responder = Sixtop::new();
+        responder
+            .add_scheduling_function(Box::new(ProposingSF {}))
+            .unwrap();
+        responder
+            .handle_msg(
+                TEST_NEIGHBOR,
+                build_request(RequestType::ADD, 1, CellList::new()),
+            )
+            .unwrap();
+
+        // RUN TEST
+        let result = responder
+            .handle_msg(
+                TEST_NEIGHBOR,
+                build_request(RequestType::ADD, 1, test_cells()),
+            )
+            .unwrap();
+
+        // ASSERT POSTCONDITION
+        let response = unwrap_response(result);
+        assert_eq!(response.header.code, ReturnCode::RC_ERR_BUSY);
+        assert!(responder.schedule().is_empty());
+        assert_eq!(
+            responder.transaction_state(TEST_NEIGHBOR),
+            TransactionState::WaitingForConfirmation
+        );
+    }
+
+    #[test]
+    fn test_simultaneous_requests() {
+        let mut node = Sixtop::new();
+        let mut other_node = Sixtop::new();
+        let mut buf = [0; MAX_MSG_SZ_BYTES];
+        let len = node
+            .add_cells(
+                TEST_RESPONDER,
+                TEST_CELL_OPTIONS,
+                1,
+                &test_cells(),
+                &mut buf,
+            )
+            .unwrap();
+        let request = deserialize_message(buf[..len].to_vec()).unwrap();
+        node.on_link_ack(TEST_RESPONDER, 0).unwrap();
+        let mut other_buf = [0; MAX_MSG_SZ_BYTES];
+        let other_len = other_node
+            .add_cells(
+                TEST_NEIGHBOR,
+                TEST_CELL_OPTIONS,
+                1,
+                &test_cells(),
+                &mut other_buf,
+            )
+            .unwrap();
+        let other_request = deserialize_message(other_buf[..other_len].to_vec()).unwrap();
+        other_node.on_link_ack(TEST_NEIGHBOR, 0).unwrap();
+
+        // RUN TEST
+        // both requests cross on the air and are turned down
+        let response = other_node
+            .handle_msg(TEST_NEIGHBOR, request)
+            .unwrap()
+            .unwrap();
+        let other_response = node
+            .handle_msg(TEST_RESPONDER, other_request)
+            .unwrap()
+            .unwrap();
+        assert_eq!(response.header().code, ReturnCode::RC_ERR_BUSY.to_u8());
+        assert_eq!(
+            other_response.header().code,
+            ReturnCode::RC_ERR_BUSY.to_u8()
+        );
+        node.handle_msg(TEST_RESPONDER, response).unwrap();
+        other_node
+            .handle_msg(TEST_NEIGHBOR, other_response)
+            .unwrap();
+        // the next transaction picks up where the turned down ones left off
+        let len = node
+            .add_cells(
+                TEST_RESPONDER,
+                TEST_CELL_OPTIONS,
+                1,
+                &test_cells(),
+                &mut buf,
+            )
+            .unwrap();
+        let request = deserialize_message(buf[..len].to_vec()).unwrap();
+        let retried = other_node.handle_msg(TEST_NEIGHBOR, request).unwrap();
+
+        // ASSERT POSTCONDITION
+        let response = unwrap_response(retried);
+        assert_eq!(response.header.code, ReturnCode::RC_SUCCESS);
+        assert_eq!(response.header.seqnum, 0);
+    }
+
+    #[test]
+    fn test_request_beyond_neighbor_capacity() {
+        let mut initiator = Sixtop::new();
+        let mut responder = Sixtop::new();
+        let count_request = || build_request(RequestType::COUNT, 0, CellList::new());
+        let known_neighbor = |index: usize| NeighborID::Short(1000 + index as u16);
+        for index in 0..MAX_NEIGHBORS {
+            responder
+                .handle_msg(known_neighbor(index), count_request())
+                .unwrap();
+        }
+        let mut buf = [0; MAX_MSG_SZ_BYTES];
+
+        // RUN TEST
+        let len = initiator
+            .count_cells(TEST_RESPONDER, TEST_CELL_OPTIONS, &mut buf)
+            .unwrap();
+        let request = deserialize_message(buf[..len].to_vec()).unwrap();
+        initiator.on_link_ack(TEST_RESPONDER, 0).unwrap();
+        let response = responder
+            .handle_msg(TEST_NEIGHBOR, request)
+            .unwrap()
+            .unwrap();
+        assert_eq!(response.header().code, ReturnCode::RC_ERR.to_u8());
+        initiator.handle_msg(TEST_RESPONDER, response).unwrap();
+
+        // ASSERT POSTCONDITION
+        assert_eq!(*initiator.seqnums.get_seqnum(TEST_RESPONDER).unwrap(), 0);
+        assert!(responder.seqnums.get_seqnum(TEST_NEIGHBOR).is_none());
+    }
+
+    #[test]
+    fn test_unexpected_response() {
+        let mut initiator = Sixtop::new();
+
+        // RUN TEST
+        let result = initiator.handle_msg(TEST_RESPONDER, SixtopMsg::ResponseMsg(Response::new()));
+
+        // ASSERT POSTCONDITION
+        assert!(result.is_err());
+    }
+
+    struct TimeoutSF {
+        timed_out: Rc<RefCell<Vec<NeighborID>>>,
+    }
+
+    impl SchedulingFunction for TimeoutSF {
+        fn sfid(&self) -> SFID {
+            DEFAULT_SFID
+        }
+
+        fn handle_timeout(
+            &mut self,
+            neighbor: NeighborID,
+            _request: &Request,
+            _sixtop: &mut SixtopHandle,
+        ) {
+            self.timed_out.borrow_mut().push(neighbor);
+        }
+    }
+
+    #[test]
+    fn test_tick_timeout() {
+        let timed_out = Rc::new(RefCell::new(Vec::new()));
+        let mut initiator = Sixtop::new();
+        initiator
+            .add_scheduling_function(Box::new(TimeoutSF {
+                timed_out: timed_out.clone(),
+            }))
+            .unwrap();
+        initiator.set_timeouts(Timeouts {
+            response: 100,
+            confirmation: 100,
+            link_ack: 100,
+        });
+        initiator.tick(1000);
+        initiator
+            .start_transaction(TEST_RESPONDER, Request::new(), &mut [0; MAX_MSG_SZ_BYTES])
+            .unwrap();
+
+        // RUN TEST
+        let not_expired = initiator.tick(1099);
+        let expired = initiator.tick(1100);
+
+        // ASSERT POSTCONDITION
+        assert!(not_expired.is_empty());
+        assert_eq!(expired, [TEST_RESPONDER]);
+        assert_eq!(*timed_out.borrow(), vec![TEST_RESPONDER]);
+        assert_eq!(
+            initiator.transaction_state(TEST_RESPONDER),
+            TransactionState::Idle
+        );
+    }
+
+    #[test]
+    fn test_request_timeout_seqnum() {
+        let mut acked = Sixtop::new();
+        let mut not_acked = Sixtop::new();
+        for initiator in [&mut acked, &mut not_acked].iter_mut() {
+            initiator.seqnums.add_neighbor(TEST_RESPONDER, 4).unwrap();
+            let mut request = Request::new();
+            request.header.seqnum = 4;
+            initiator
+                .start_transaction(TEST_RESPONDER, request, &mut [0; MAX_MSG_SZ_BYTES])
+                .unwrap();
+        }
+        acked.on_link_ack(TEST_RESPONDER, 4).unwrap();
+
+        // RUN TEST
+        acked.tick(Timeouts::default().response);
+        not_acked.tick(Timeouts::default().response);
+
+        // ASSERT POSTCONDITION
+        // the responder only incremented its SeqNum if it got the request
+        assert_eq!(*acked.seqnums.get_seqnum(TEST_RESPONDER).unwrap(), 5);
+        assert_eq!(*not_acked.seqnums.get_seqnum(TEST_RESPONDER).unwrap(), 4);
+    }
+
+    #[test]
+    fn test_handle_delete_request_wrong_neighbor() {
+        let mut sixtop = Sixtop::new();
+        for cell in test_cells() {
+            sixtop
+                .schedule
+                .add_cell(
+                    TEST_SLOTFRAME,
+                    cell,
+                    TEST_OTHER_NEIGHBOR,
+                    TEST_MIRRORED_CELL_OPTIONS,
+                )
+                .unwrap();
+        }
+
+        // RUN TEST
+        let result = sixtop
+            .handle_msg(
+                TEST_NEIGHBOR,
+                build_request(RequestType::DELETE, 2, test_cells()),
+            )
+            .unwrap();
+
+        // ASSERT POSTCONDITION
+        let response = unwrap_response(result);
+        assert_eq!(response.header.code, ReturnCode::RC_ERR_CELLLIST);
+        assert_eq!(sixtop.schedule().len(), 2);
+    }
+
+    /// what RecordingSF was told
+    #[derive(Debug, PartialEq)]
+    enum SfEvent {
+        Request(NeighborID, RequestType, ReturnCode),
+        Answered(NeighborID, RequestType, ReturnCode, CellList),
+        Counted(NeighborID, ReturnCode, u16),
+        Signaled(NeighborID, ReturnCode, Payload),
+        Cleared(NeighborID, RequestType),
+        NotStarted(NeighborID, SixtopError),
+        NotDelivered(NeighborID),
+        SeqNumError(NeighborID),
+    }
+
+    /// Records what it's told, picks the last of the proposed cells and clears the schedule
+    /// with neighbors whose SeqNum is off.
+    struct RecordingSF {
+        events: Rc<RefCell<Vec<SfEvent>>>,
+    }
+
+    impl SchedulingFunction for RecordingSF {
+        fn sfid(&self) -> SFID {
+            DEFAULT_SFID
+        }
+
+        fn pick_cells(
+            &mut self,
+            _neighbor: NeighborID,
+            request: &Request,
+            candidates: &[Cell],
+        ) -> CellList {
+            candidates
+                .iter()
+                .rev()
+                .take(request.num_cells as usize)
+                .copied()
+                .collect()
+        }
+
+        fn handle_request(
+            &mut self,
+            neighbor: NeighborID,
+            request: &Request,
+            code: ReturnCode,
+            _sixtop: &mut SixtopHandle,
+        ) {
+            let event = SfEvent::Request(neighbor, request.header.code, code);
+            self.events.borrow_mut().push(event);
+        }
+
+        fn handle_outcome(
+            &mut self,
+            neighbor: NeighborID,
+            request: &Request,
+            outcome: TransactionOutcome,
+            _sixtop: &mut SixtopHandle,
+        ) {
+            let event = match outcome {
+                TransactionOutcome::Answered { code, cell_list } => SfEvent::Answered(
+                    neighbor,
+                    request.header.code,
+                    code,
+                    CellList::from_slice(cell_list).unwrap(),
+                ),
+                TransactionOutcome::Counted { code, num_cells } => {
+                    SfEvent::Counted(neighbor, code, num_cells)
+                }
+                TransactionOutcome::Signaled { code, payload } => {
+                    SfEvent::Signaled(neighbor, code, Payload::from_slice(payload).unwrap())
+                }
+                TransactionOutcome::Cleared => SfEvent::Cleared(neighbor, request.header.code),
+                TransactionOutcome::NotStarted(error) => SfEvent::NotStarted(neighbor, error),
+                TransactionOutcome::NotDelivered => SfEvent::NotDelivered(neighbor),
+            };
+            self.events.borrow_mut().push(event);
+        }
+
+        fn handle_seqnum_error(&mut self, neighbor: NeighborID, sixtop: &mut SixtopHandle) {
+            self.events
+                .borrow_mut()
+                .push(SfEvent::SeqNumError(neighbor));
+            sixtop.clear(neighbor).unwrap();
+        }
+    }
+
+    fn recording_sixtop() -> (Sixtop, Rc<RefCell<Vec<SfEvent>>>) {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let mut sixtop = Sixtop::new();
+        sixtop
+            .add_scheduling_function(Box::new(RecordingSF {
+                events: events.clone(),
+            }))
+            .unwrap();
+        (sixtop, events)
+    }
+
+    #[test]
+    fn test_sf_two_step_outcome() {
+        let (mut initiator, events) = recording_sixtop();
+        let mut responder = Sixtop::new();
+
+        // RUN TEST
+        let mut buf = [0; MAX_MSG_SZ_BYTES];
+        let len = initiator
+            .add_cells(
+                TEST_RESPONDER,
+                TEST_CELL_OPTIONS,
+                2,
+                &test_cells(),
+                &mut buf,
+            )
+            .unwrap();
+        let response = responder
+            .handle_msg(
+                TEST_NEIGHBOR,
+                deserialize_message(buf[..len].to_vec()).unwrap(),
+            )
+            .unwrap()
+            .unwrap();
+        initiator.handle_msg(TEST_RESPONDER, response).unwrap();
+
+        // ASSERT POSTCONDITION
+        assert_eq!(
+            *events.borrow(),
+            vec![SfEvent::Answered(
+                TEST_RESPONDER,
+                RequestType::ADD,
+                ReturnCode::RC_SUCCESS,
+                test_cells()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_sf_pick_cells() {
+        let (mut initiator, events) = recording_sixtop();
+        let mut responder = Sixtop::new();
+        responder
+            .add_scheduling_function(Box::new(ProposingSF {}))
+            .unwrap();
+
+        // RUN TEST
+        let mut buf = [0; MAX_MSG_SZ_BYTES];
+        let len = initiator
+            .add_cells(TEST_RESPONDER, TEST_CELL_OPTIONS, 1, &[], &mut buf)
+            .unwrap();
+        let response = responder
+            .handle_msg(
+                TEST_NEIGHBOR,
+                deserialize_message(buf[..len].to_vec()).unwrap(),
+            )
+            .unwrap()
+            .unwrap();
+        let confirmation = initiator
+            .handle_msg(TEST_RESPONDER, response)
+            .unwrap()
+            .unwrap();
+        let outcome_before_ack = events.borrow().len();
+        initiator.on_link_ack(TEST_RESPONDER, 0).unwrap();
+
+        // ASSERT POSTCONDITION
+        let picked = CellList::from_slice(&test_cells()[1..]).unwrap();
+        if let SixtopMsg::ConfirmationMsg(confirmation) = confirmation {
+            assert_eq!(confirmation.cell_list, picked);
+        } else {
+            panic!("expected a confirmation");
+        }
+        // the transaction only ends once the confirmation made it
+        assert_eq!(outcome_before_ack, 0);
+        assert_eq!(
+            *events.borrow(),
+            vec![SfEvent::Answered(
+                TEST_RESPONDER,
+                RequestType::ADD,
+                ReturnCode::RC_SUCCESS,
+                picked
+            )]
+        );
+    }
+
+    #[test]
+    fn test_sf_pick_candidates() {
+        let (mut responder, events) = recording_sixtop();
+
+        // RUN TEST
+        let result = responder
+            .handle_msg(
+                TEST_NEIGHBOR,
+                build_request(RequestType::ADD, 1, test_cells()),
+            )
+            .unwrap();
+
+        // ASSERT POSTCONDITION
+        let response = unwrap_response(result);
+        let picked = CellList::from_slice(&test_cells()[1..]).unwrap();
+        assert_eq!(response.header.code, ReturnCode::RC_SUCCESS);
+        assert_eq!(response.cell_list, picked);
+        assert_eq!(
+            *events.borrow(),
+            vec![SfEvent::Request(
+                TEST_NEIGHBOR,
+                RequestType::ADD,
+                ReturnCode::RC_SUCCESS
+            )]
+        );
+    }
+
+    #[test]
+    fn test_sf_seqnum_error_clear() {
+        let (mut initiator, events) = recording_sixtop();
+        let mut buf = [0; MAX_MSG_SZ_BYTES];
+        initiator
+            .add_cells(
+                TEST_RESPONDER,
+                TEST_CELL_OPTIONS,
+                2,
+                &test_cells(),
+                &mut buf,
+            )
+            .unwrap();
+        let mut response = Response::new();
+        response.header.code = ReturnCode::RC_ERR_SEQNUM;
+
+        // RUN TEST
+        initiator
+            .handle_msg(TEST_RESPONDER, SixtopMsg::ResponseMsg(response))
+            .unwrap();
+        let result = initiator.poll_sf_request(&mut buf);
+
+        // ASSERT POSTCONDITION
+        assert_eq!(*events.borrow(), vec![SfEvent::SeqNumError(TEST_RESPONDER)]);
+        let len = match result {
+            Some((TEST_RESPONDER, Ok(len))) => len,
+            _ => panic!("expected a CLEAR request"),
+        };
+        match deserialize_message(buf[..len].to_vec()).unwrap() {
+            SixtopMsg::RequestMsg(request) => {
+                assert_eq!(request.header.code, RequestType::CLEAR)
+            }
+            _ => panic!("expected a CLEAR request"),
+        }
+        assert_eq!(
+            initiator.transaction_state(TEST_RESPONDER),
+            TransactionState::WaitingForResponse
+        );
+        assert!(initiator.poll_sf_request(&mut buf).is_none());
+    }
+
+    #[test]
+    fn test_sf_request_not_started() {
+        let (mut initiator, events) = recording_sixtop();
+        let mut buf = [0; MAX_MSG_SZ_BYTES];
+        initiator.clear(TEST_RESPONDER, &mut buf).unwrap();
+        SixtopHandle::new(DEFAULT_SFID, 0, &mut initiator.sf_requests)
+            .add_cells(TEST_RESPONDER, TEST_CELL_OPTIONS, 1, &test_cells())
+            .unwrap();
+
+        // RUN TEST
+        let result = initiator.poll_sf_request(&mut buf);
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result, Some((TEST_RESPONDER, Err(SixtopError::Busy))));
+        assert_eq!(
+            *events.borrow(),
+            vec![SfEvent::NotStarted(TEST_RESPONDER, SixtopError::Busy)]
+        );
+    }
+
+    #[test]
+    fn test_sf_not_delivered() {
+        let (mut initiator, events) = recording_sixtop();
+        let mut buf = [0; MAX_MSG_SZ_BYTES];
+        initiator
+            .add_cells(
+                TEST_RESPONDER,
+                TEST_CELL_OPTIONS,
+                2,
+                &test_cells(),
+                &mut buf,
+            )
+            .unwrap();
+
+        // RUN TEST
+        initiator.on_link_nack(TEST_RESPONDER, 0).unwrap();
+
+        // ASSERT POSTCONDITION
+        assert_eq!(
+            *events.borrow(),
+            vec![SfEvent::NotDelivered(TEST_RESPONDER)]
+        );
+    }
+
+    #[test]
+    fn test_sf_handle_request() {
+        let (mut responder, events) = recording_sixtop();
+
+        // RUN TEST
+        responder
+            .handle_msg(
+                TEST_NEIGHBOR,
+                build_request(RequestType::DELETE, 1, test_cells()),
+            )
+            .unwrap();
+
+        // ASSERT POSTCONDITION
+        assert_eq!(
+            *events.borrow(),
+            vec![SfEvent::Request(
+                TEST_NEIGHBOR,
+                RequestType::DELETE,
+                ReturnCode::RC_ERR_CELLLIST
+            )]
+        );
+    }
+
+    #[test]
+    fn test_sf_count_outcome() {
+        let (mut initiator, events) = recording_sixtop();
+        let mut responder = Sixtop::new();
+        for cell in test_cells() {
+            responder
+                .schedule
+                .add_cell(
+                    TEST_SLOTFRAME,
+                    cell,
+                    TEST_NEIGHBOR,
+                    TEST_MIRRORED_CELL_OPTIONS,
+                )
+                .unwrap();
+        }
+        let mut buf = [0; MAX_MSG_SZ_BYTES];
+        let len = initiator
+            .count_cells(TEST_RESPONDER, TEST_CELL_OPTIONS, &mut buf)
+            .unwrap();
+        let response = responder
+            .handle_msg(
+                TEST_NEIGHBOR,
+                deserialize_message(buf[..len].to_vec()).unwrap(),
+            )
+            .unwrap()
+            .unwrap();
+        let data = serialize_message(response).unwrap();
+
+        // RUN TEST
+        let response = initiator.parse_msg(TEST_RESPONDER, &data).unwrap();
+        let result = initiator
+            .handle_msg(TEST_RESPONDER, response.try_into().unwrap())
+            .unwrap();
+
+        // ASSERT POSTCONDITION
+        assert!(result.is_none());
+        // without context, the NumCells field looks like half a cell
+        assert_eq!(
+            deserialize_message(data).err(),
+            Some(SixtopError::TruncatedCellList)
+        );
+        assert_eq!(
+            *events.borrow(),
+            vec![SfEvent::Counted(TEST_RESPONDER, ReturnCode::RC_SUCCESS, 2)]
+        );
+        assert_eq!(
+            initiator.transaction_state(TEST_RESPONDER),
+            TransactionState::Idle
+        );
+    }
+
+    #[test]
+    fn test_count_response_to_add_request() {
+        let mut initiator = Sixtop::new();
+        initiator
+            .add_cells(
+                TEST_RESPONDER,
+                TEST_CELL_OPTIONS,
+                1,
+                &test_cells(),
+                &mut [0; MAX_MSG_SZ_BYTES],
+            )
+            .unwrap();
+        let mut response = CountResponse::new();
+        response.num_cells = 1;
+
+        // RUN TEST
+        let result = initiator.handle_msg(TEST_RESPONDER, SixtopMsg::CountResponseMsg(response));
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result.err(), Some(SixtopError::InvalidResponse));
+        assert_eq!(
+            initiator.transaction_state(TEST_RESPONDER),
+            TransactionState::WaitingForResponse
+        );
+    }
+
+    #[test]
+    fn test_sf_clear_aborts_transaction() {
+        let (mut sixtop, events) = recording_sixtop();
+        sixtop
+            .add_cells(
+                TEST_NEIGHBOR,
+                TEST_CELL_OPTIONS,
+                1,
+                &test_cells(),
+                &mut [0; MAX_MSG_SZ_BYTES],
+            )
+            .unwrap();
+        let mut request = Request::new();
+        request.header.code = RequestType::CLEAR;
+
+        // RUN TEST
+        let result = sixtop
+            .handle_msg(TEST_NEIGHBOR, SixtopMsg::RequestMsg(request))
+            .unwrap();
+
+        // ASSERT POSTCONDITION
+        let response = unwrap_response(result);
+        assert_eq!(response.header.code, ReturnCode::RC_SUCCESS);
+        assert_eq!(
+            sixtop.transaction_state(TEST_NEIGHBOR),
+            TransactionState::Idle
+        );
+        assert_eq!(
+            *events.borrow(),
+            vec![
+                SfEvent::Cleared(TEST_NEIGHBOR, RequestType::ADD),
+                SfEvent::Request(TEST_NEIGHBOR, RequestType::CLEAR, ReturnCode::RC_SUCCESS)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sf_signal_outcome() {
+        let (mut initiator, events) = recording_sixtop();
+        let mut responder = Sixtop::new();
+        responder
+            .add_scheduling_function(Box::new(EchoSF { sfid: DEFAULT_SFID }))
+            .unwrap();
+        let mut buf = [0; MAX_MSG_SZ_BYTES];
+        let len = initiator
+            .signal(TEST_RESPONDER, &[0xCA, 0xFE, 0x01], &mut buf)
+            .unwrap();
+        let response = responder
+            .handle_msg(
+                TEST_NEIGHBOR,
+                deserialize_message(buf[..len].to_vec()).unwrap(),
+            )
+            .unwrap()
+            .unwrap();
+        let data = serialize_message(response).unwrap();
+
+        // RUN TEST
+        let response = initiator.parse_msg(TEST_RESPONDER, &data).unwrap();
+        let result = initiator
+            .handle_msg(TEST_RESPONDER, response.try_into().unwrap())
+            .unwrap();
+
+        // ASSERT POSTCONDITION
+        assert!(result.is_none());
+        assert_eq!(
+            *events.borrow(),
+            vec![SfEvent::Signaled(
+                TEST_RESPONDER,
+                ReturnCode::RC_SUCCESS,
+                Payload::from_slice(&[0, 22, 0xCA, 0xFE, 0x01]).unwrap()
+            )]
+        );
+        assert_eq!(
+            initiator.transaction_state(TEST_RESPONDER),
+            TransactionState::Idle
+        );
+    }
+
+    #[test]
+    fn test_neighbor_churn() {
+        let mut sixtop = Sixtop::new();
+        let count_request = || build_request(RequestType::COUNT, 0, CellList::new());
+        // far away from TEST_NEIGHBOR and TEST_OTHER_NEIGHBOR
+        let known_neighbor = |index: usize| NeighborID::Short(1000 + index as u16);
+        for index in 0..MAX_NEIGHBORS {
+            sixtop
+                .handle_msg(known_neighbor(index), count_request())
+                .unwrap();
+        }
+        let mut clear_request = Request::new();
+        clear_request.header.code = RequestType::CLEAR;
+
+        // RUN TEST
+        let full = sixtop.handle_msg(TEST_NEIGHBOR, count_request()).unwrap();
+        sixtop
+            .handle_msg(known_neighbor(0), SixtopMsg::RequestMsg(clear_request))
+            .unwrap();
+        let after_clear = sixtop.handle_msg(TEST_NEIGHBOR, count_request()).unwrap();
+        sixtop.remove_neighbor(known_neighbor(1));
+        let after_remove = sixtop
+            .handle_msg(TEST_OTHER_NEIGHBOR, count_request())
+            .unwrap();
+
+        // ASSERT POSTCONDITION
+        assert_eq!(full.unwrap().header().code, ReturnCode::RC_ERR.to_u8());
+        assert_eq!(
+            after_clear.unwrap().header().code,
+            ReturnCode::RC_SUCCESS.to_u8()
+        );
+        assert_eq!(
+            after_remove.unwrap().header().code,
+            ReturnCode::RC_SUCCESS.to_u8()
+        );
+    }
+
+    #[test]
+    fn test_remove_neighbor_aborts_transaction() {
+        let (mut sixtop, events) = recording_sixtop();
+        sixtop
+            .schedule
+            .add_cell(
+                TEST_SLOTFRAME,
+                test_cells()[0],
+                TEST_NEIGHBOR,
+                TEST_CELL_OPTIONS,
+            )
+            .unwrap();
+        sixtop
+            .count_cells(TEST_NEIGHBOR, TEST_CELL_OPTIONS, &mut [0; MAX_MSG_SZ_BYTES])
+            .unwrap();
+
+        // RUN TEST
+        sixtop.remove_neighbor(TEST_NEIGHBOR);
+
+        // ASSERT POSTCONDITION
+        assert!(sixtop.schedule().is_empty());
+        assert_eq!(
+            sixtop.transaction_state(TEST_NEIGHBOR),
+            TransactionState::Idle
+        );
+        assert_eq!(sixtop.seqnums.get_seqnum(TEST_NEIGHBOR), None);
+        assert_eq!(
+            *events.borrow(),
+            vec![SfEvent::Cleared(TEST_NEIGHBOR, RequestType::COUNT)]
+        );
+    }
+
+    #[test]
+    fn test_sf_handle_count_list_signal() {
+        let (mut initiator, _) = recording_sixtop();
+        let mut sixtop = SixtopHandle::new(DEFAULT_SFID, 7, &mut initiator.sf_requests);
+        sixtop
+            .count_cells(TEST_NEIGHBOR, TEST_CELL_OPTIONS)
+            .unwrap();
+        sixtop
+            .list_cells(TEST_OTHER_NEIGHBOR, TEST_CELL_OPTIONS, 2, 5)
+            .unwrap();
+        sixtop.signal(TEST_RESPONDER, &[0xCA, 0xFE]).unwrap();
+
+        // RUN TEST
+        let mut requests = Vec::new();
+        let mut buf = [0; MAX_MSG_SZ_BYTES];
+        while let Some((neighbor, result)) = initiator.poll_sf_request(&mut buf) {
+            let len = result.unwrap();
+            match deserialize_message(buf[..len].to_vec()).unwrap() {
+                SixtopMsg::RequestMsg(request) => requests.push((neighbor, request)),
+                _ => panic!("expected a request"),
+            }
+        }
+
+        // ASSERT POSTCONDITION
+        assert_eq!(requests.len(), 3);
+        let (neighbor, count) = &requests[0];
+        assert_eq!(*neighbor, TEST_NEIGHBOR);
+        assert_eq!(count.header.code, RequestType::COUNT);
+        assert_eq!(count.metadata, 7);
+        assert_eq!(count.cell_options, TEST_CELL_OPTIONS);
+        let (neighbor, list) = &requests[1];
+        assert_eq!(*neighbor, TEST_OTHER_NEIGHBOR);
+        assert_eq!(list.header.code, RequestType::LIST);
+        assert_eq!((list.offset, list.max_num_cells), (2, 5));
+        let (neighbor, signal) = &requests[2];
+        assert_eq!(*neighbor, TEST_RESPONDER);
+        assert_eq!(signal.header.code, RequestType::SIGNAl);
+        assert_eq!(signal.payload, [0xCA, 0xFE]);
+    }
 }
@@ -0,0 +1,246 @@
+/**
+ * Initiator side of the LIST command: a neighbor's answer to a LIST request only contains a page
+ * of its schedule, so we keep asking for the next page until it answers with RC_EOL.
+ */
+use crate::error::SixtopError;
+use crate::schedule::ScheduleCellList;
+use crate::types::{CellOptions, NeighborID, RequestType, Response, ReturnCode};
+use crate::{list_request, new_request, Sixtop};
+
+#[derive(Debug)]
+pub struct ListPager {
+    metadata: u16,
+    cell_options: CellOptions,
+    max_num_cells: u16,
+    offset: u16,
+    cell_list: ScheduleCellList,
+    done: bool,
+}
+
+impl ListPager {
+    /// returns Err if `max_num_cells` is 0, the neighbor couldn't answer with anything but
+    ///         empty pages then
+    pub fn new(
+        metadata: u16,
+        cell_options: CellOptions,
+        max_num_cells: u16,
+    ) -> Result<ListPager, SixtopError> {
+        if max_num_cells == 0 {
+            return Err(SixtopError::ZeroMaxNumCells);
+        }
+
+        Ok(ListPager {
+            metadata,
+            cell_options,
+            max_num_cells,
+            offset: 0,
+            cell_list: ScheduleCellList::new(),
+            done: false,
+        })
+    }
+
+    /// Start the transaction that asks `neighbor` for the next page, see
+    /// Sixtop::start_transaction().
+    ///
+    /// returns the length of the LIST request, which was written to `buf`, or None if we've
+    ///         seen all pages already,
+    ///         Err if the transaction couldn't be started
+    pub fn request_next_page(
+        &self,
+        sixtop: &mut Sixtop,
+        neighbor: NeighborID,
+        buf: &mut [u8],
+    ) -> Result<Option<usize>, SixtopError> {
+        if self.done {
+            return Ok(None);
+        }
+
+        let request = list_request(
+            new_request(RequestType::LIST, sixtop.sfid, self.metadata),
+            self.cell_options,
+            self.offset,
+            self.max_num_cells,
+        );
+        sixtop.start_transaction(neighbor, request, buf).map(Some)
+    }
+
+    /// Collect the cells of the response to our last request.
+    ///
+    /// returns Ok(true) once the neighbor signalled the end of its list,
+    ///         Ok(false) if there are more pages to request,
+    ///         Err if the neighbor answered with an error or we can't hold any more cells
+    pub fn handle_response(&mut self, response: &Response) -> Result<bool, SixtopError> {
+        let code = response.header.code;
+        if code != ReturnCode::RC_SUCCESS && code != ReturnCode::RC_EOL {
+            return Err(SixtopError::ErrorResponse(code));
+        }
+
+        // a page without cells that isn't the last one would make us ask for it forever
+        if code == ReturnCode::RC_SUCCESS && response.cell_list.is_empty() {
+            return Err(SixtopError::InvalidResponse);
+        }
+
+        self.cell_list
+            .extend_from_slice(&response.cell_list)
+            .map_err(|_| SixtopError::CapacityExceeded)?;
+        self.offset = self.offset.saturating_add(response.cell_list.len() as u16);
+        self.done = code == ReturnCode::RC_EOL;
+
+        Ok(self.done)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// The cells collected so far.
+    pub fn cell_list(&self) -> &ScheduleCellList {
+        &self.cell_list
+    }
+
+    pub fn into_cell_list(self) -> ScheduleCellList {
+        self.cell_list
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msg_builder::MAX_MSG_SZ_BYTES;
+    use crate::msg_reader::parse_message;
+    use crate::schedule::SlotframeID;
+    use crate::types::{Cell, Msg, Request, SixtopMsg, DEFAULT_SFID};
+    use core::convert::TryInto;
+
+    const TEST_NEIGHBOR: NeighborID = NeighborID::Short(22);
+    const TEST_RESPONDER: NeighborID = NeighborID::Short(33);
+    const TEST_CELL_OPTIONS: CellOptions = CellOptions::TX;
+    const TEST_SLOTFRAME: SlotframeID = 0;
+
+    fn parse_request(data: &[u8]) -> Request {
+        match parse_message(data).unwrap().try_into().unwrap() {
+            SixtopMsg::RequestMsg(request) => request,
+            _ => panic!("expected a request"),
+        }
+    }
+
+    #[test]
+    fn test_new_zero_max_num_cells() {
+        // RUN TEST
+        let result = ListPager::new(0, TEST_CELL_OPTIONS, 0);
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result.err(), Some(SixtopError::ZeroMaxNumCells));
+    }
+
+    #[test]
+    fn test_request_next_page() {
+        let mut sixtop = Sixtop::new();
+        sixtop.set_metadata(7);
+        let test_pager = ListPager::new(0, TEST_CELL_OPTIONS, 3).unwrap();
+        let mut buf = [0; MAX_MSG_SZ_BYTES];
+
+        // RUN TEST
+        let len = test_pager
+            .request_next_page(&mut sixtop, TEST_RESPONDER, &mut buf)
+            .unwrap()
+            .unwrap();
+
+        // ASSERT POSTCONDITION
+        let request = parse_request(&buf[..len]);
+        assert_eq!(request.header.code, RequestType::LIST);
+        assert_eq!(request.header.sfid, DEFAULT_SFID);
+        assert_eq!(
+            Some(request.header.seqnum),
+            sixtop.transaction_seqnum(TEST_RESPONDER)
+        );
+        assert_eq!(request.metadata, 0);
+        assert_eq!(request.cell_options, TEST_CELL_OPTIONS);
+        assert_eq!(request.offset, 0);
+        assert_eq!(request.max_num_cells, 3);
+        assert_eq!(
+            sixtop.pending_request_type(TEST_RESPONDER),
+            Some(RequestType::LIST)
+        );
+    }
+
+    #[test]
+    fn test_request_next_page_busy() {
+        let mut sixtop = Sixtop::new();
+        let test_pager = ListPager::new(0, TEST_CELL_OPTIONS, 3).unwrap();
+        let mut buf = [0; MAX_MSG_SZ_BYTES];
+        test_pager
+            .request_next_page(&mut sixtop, TEST_RESPONDER, &mut buf)
+            .unwrap();
+
+        // RUN TEST
+        let result = test_pager.request_next_page(&mut sixtop, TEST_RESPONDER, &mut buf);
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result, Err(SixtopError::Busy));
+    }
+
+    #[test]
+    fn test_handle_response_error() {
+        let mut test_pager = ListPager::new(0, TEST_CELL_OPTIONS, 3).unwrap();
+        let mut response = Response::new();
+        response.header.code = ReturnCode::RC_ERR;
+
+        // RUN TEST
+        let result = test_pager.handle_response(&response);
+
+        // ASSERT POSTCONDITION
+        assert!(result.is_err());
+        assert!(!test_pager.is_done());
+    }
+
+    #[test]
+    fn test_walk_all_pages() {
+        let mut initiator = Sixtop::new();
+        let mut responder = Sixtop::new();
+        let cells: ScheduleCellList = (0..5)
+            .map(|slot_offset| Cell {
+                slot_offset,
+                channel_offset: 1,
+            })
+            .collect();
+        for cell in cells.iter() {
+            responder
+                .schedule
+                .add_cell(
+                    TEST_SLOTFRAME,
+                    *cell,
+                    TEST_NEIGHBOR,
+                    TEST_CELL_OPTIONS.mirrored(),
+                )
+                .unwrap();
+        }
+        let mut test_pager = ListPager::new(0, TEST_CELL_OPTIONS, 2).unwrap();
+        let mut buf = [0; MAX_MSG_SZ_BYTES];
+
+        // RUN TEST
+        let mut num_requests = 0;
+        while let Some(len) = test_pager
+            .request_next_page(&mut initiator, TEST_RESPONDER, &mut buf)
+            .unwrap()
+        {
+            let request = parse_request(&buf[..len]);
+            let reply = responder
+                .handle_msg(TEST_NEIGHBOR, SixtopMsg::RequestMsg(request))
+                .unwrap();
+            if let Some(SixtopMsg::ResponseMsg(response)) = &reply {
+                test_pager.handle_response(response).unwrap();
+            } else {
+                panic!("expected a response");
+            }
+            initiator
+                .handle_msg(TEST_RESPONDER, reply.unwrap())
+                .unwrap();
+            num_requests += 1;
+        }
+
+        // ASSERT POSTCONDITION
+        assert_eq!(num_requests, 3);
+        assert_eq!(test_pager.into_cell_list(), cells);
+    }
+}
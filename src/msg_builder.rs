@@ -1,45 +1,253 @@
-use std::vec::Vec;
+/**
+ * Encoding of 6P messages. The messages implement scroll's TryIntoCtx, so they can be written
+ * with Pwrite straight into a frame buffer at any offset, e.g. `frame.gwrite(&request, offset)`.
+ * The write_*() functions write them to the start of a buffer; the serialize_*() functions that
+ * return a Vec are only available with std.
+ */
+use scroll::ctx::TryIntoCtx;
+use scroll::{Pwrite, LE};
 
-use crate::types::{CellList, Request, Response};
+use crate::error::SixtopError;
+use crate::msg_ref::CELL_SZ_BYTES;
+use crate::types::{
+    Cell, Confirmation, CountResponse, MsgHdr, Request, RequestType, Response, SignalResponse,
+    SixtopMsg, MAX_NUM_CELLS, SIXTOP_HDR_SZ_BYTES,
+};
 
-fn serialize_cell_list(cell_list: CellList) -> Result<Vec<u8>, ()> {
-    let mut bytes = Vec::new();
+/// Size of the largest message we can build: a RELOCATE request with full cell lists.
+pub const MAX_MSG_SZ_BYTES: usize = SIXTOP_HDR_SZ_BYTES + 4 + 2 * MAX_NUM_CELLS * CELL_SZ_BYTES;
 
+/// scroll refuses to write anything at the very end of the buffer, even nothing at all.
+fn gwrite_bytes(dst: &mut [u8], bytes: &[u8], offset: &mut usize) -> Result<(), SixtopError> {
+    if !bytes.is_empty() {
+        dst.gwrite(bytes, offset)?;
+    }
+    Ok(())
+}
+
+fn gwrite_cell_list(
+    dst: &mut [u8],
+    cell_list: &[Cell],
+    offset: &mut usize,
+) -> Result<(), SixtopError> {
     for cell in cell_list {
-        bytes.extend_from_slice(&cell.slot_offset.to_le_bytes());
-        bytes.extend_from_slice(&cell.channel_offset.to_le_bytes());
+        dst.gwrite(cell, offset)?;
+    }
+    Ok(())
+}
+
+impl<C: Copy + Into<u8>> TryIntoCtx for &MsgHdr<C> {
+    type Error = SixtopError;
+
+    fn try_into_ctx(self, dst: &mut [u8], _: ()) -> Result<usize, SixtopError> {
+        Ok(dst.pwrite(&self.to_bytes()[..], 0)?)
     }
+}
 
-    Ok(bytes)
+impl TryIntoCtx for &Cell {
+    type Error = SixtopError;
+
+    fn try_into_ctx(self, dst: &mut [u8], _: ()) -> Result<usize, SixtopError> {
+        let offset = &mut 0;
+        dst.gwrite_with(self.slot_offset, offset, LE)?;
+        dst.gwrite_with(self.channel_offset, offset, LE)?;
+        Ok(*offset)
+    }
 }
 
 // TODO could these just be struct impls?
-pub fn serialize_request(request: Request) -> Result<Vec<u8>, ()> {
-    // TODO do we want to do some sort of coherence check for the msg type and code fields?
-    let mut header = request.header.serialize().unwrap();
+impl TryIntoCtx for &Request {
+    type Error = SixtopError;
 
-    let mut payload = Vec::new();
-    payload.extend_from_slice(&request.metadata.to_le_bytes());
-    payload.push(request.cell_options);
-    payload.push(request.num_cells);
-    payload.extend_from_slice(&serialize_cell_list(request.cell_list).unwrap());
+    fn try_into_ctx(self, dst: &mut [u8], _: ()) -> Result<usize, SixtopError> {
+        // TODO do we want to do some sort of coherence check for the msg type and code fields?
+        // the same checks as in msg_reader, so that we don't send what we'd reject ourselves
+        let num_cells = self.num_cells as usize;
+        match self.header.code {
+            RequestType::ADD | RequestType::DELETE
+                if !self.cell_list.is_empty() && self.cell_list.len() < num_cells =>
+            {
+                return Err(SixtopError::NumCellsMismatch);
+            }
+            RequestType::RELOCATE
+                if self
+                    .relocation_cell_list
+                    .as_ref()
+                    .map_or(0, |list| list.len())
+                    < num_cells =>
+            {
+                return Err(SixtopError::NumCellsMismatch);
+            }
+            _ => {}
+        }
+
+        let offset = &mut 0;
+        dst.gwrite(&self.header, offset)?;
+        dst.gwrite_with(self.metadata, offset, LE)?;
+
+        match self.header.code {
+            RequestType::CLEAR | RequestType::Reserved | RequestType::Unassigned(_) => {
+                // CLEAR requests only carry the Metadata, and we don't know what else others do
+            }
+            RequestType::SIGNAl => {
+                gwrite_bytes(dst, &self.payload, offset)?;
+            }
+            RequestType::COUNT => {
+                // COUNT requests end after the CellOptions
+                dst.gwrite(self.cell_options.bits(), offset)?;
+            }
+            RequestType::LIST => {
+                dst.gwrite(self.cell_options.bits(), offset)?;
+                dst.gwrite(0u8, offset)?; // Reserved
+                dst.gwrite_with(self.offset, offset, LE)?;
+                dst.gwrite_with(self.max_num_cells, offset, LE)?;
+            }
+            _ => {
+                dst.gwrite(self.cell_options.bits(), offset)?;
+                dst.gwrite(self.num_cells, offset)?;
+                if let Some(relocation_cell_list) = &self.relocation_cell_list {
+                    gwrite_cell_list(dst, relocation_cell_list, offset)?;
+                }
+                gwrite_cell_list(dst, &self.cell_list, offset)?;
+            }
+        }
+
+        Ok(*offset)
+    }
+}
+
+impl TryIntoCtx for &Response {
+    type Error = SixtopError;
+
+    fn try_into_ctx(self, dst: &mut [u8], _: ()) -> Result<usize, SixtopError> {
+        // TODO do we want to do some sort of coherence check for the msg type and code fields?
+        let offset = &mut 0;
+        dst.gwrite(&self.header, offset)?;
+        gwrite_cell_list(dst, &self.cell_list, offset)?;
+        Ok(*offset)
+    }
+}
+
+impl TryIntoCtx for &Confirmation {
+    type Error = SixtopError;
+
+    fn try_into_ctx(self, dst: &mut [u8], _: ()) -> Result<usize, SixtopError> {
+        let offset = &mut 0;
+        dst.gwrite(&self.header, offset)?;
+        gwrite_cell_list(dst, &self.cell_list, offset)?;
+        Ok(*offset)
+    }
+}
+
+impl TryIntoCtx for &CountResponse {
+    type Error = SixtopError;
+
+    fn try_into_ctx(self, dst: &mut [u8], _: ()) -> Result<usize, SixtopError> {
+        let offset = &mut 0;
+        dst.gwrite(&self.header, offset)?;
+        dst.gwrite_with(self.num_cells, offset, LE)?;
+        Ok(*offset)
+    }
+}
+
+impl TryIntoCtx for &SignalResponse {
+    type Error = SixtopError;
+
+    fn try_into_ctx(self, dst: &mut [u8], _: ()) -> Result<usize, SixtopError> {
+        let offset = &mut 0;
+        dst.gwrite(&self.header, offset)?;
+        gwrite_bytes(dst, &self.payload, offset)?;
+        Ok(*offset)
+    }
+}
+
+impl TryIntoCtx for &SixtopMsg {
+    type Error = SixtopError;
+
+    fn try_into_ctx(self, dst: &mut [u8], _: ()) -> Result<usize, SixtopError> {
+        match self {
+            SixtopMsg::RequestMsg(request) => dst.pwrite(request, 0),
+            SixtopMsg::ResponseMsg(response) => dst.pwrite(response, 0),
+            SixtopMsg::CountResponseMsg(response) => dst.pwrite(response, 0),
+            SixtopMsg::SignalResponseMsg(response) => dst.pwrite(response, 0),
+            SixtopMsg::ConfirmationMsg(confirmation) => dst.pwrite(confirmation, 0),
+        }
+    }
+}
+
+pub fn write_request(request: &Request, buf: &mut [u8]) -> Result<usize, SixtopError> {
+    buf.pwrite(request, 0)
+}
 
-    header.extend_from_slice(&payload);
-    Ok(header)
+pub fn write_response(response: &Response, buf: &mut [u8]) -> Result<usize, SixtopError> {
+    buf.pwrite(response, 0)
 }
 
-pub fn serialize_response(response: Response) -> Result<Vec<u8>, ()> {
-    // TODO do we want to do some sort of coherence check for the msg type and code fields?
-    let mut header = response.header.serialize().unwrap();
-    let payload = serialize_cell_list(response.cell_list).unwrap();
-    header.extend_from_slice(&payload);
-    Ok(header)
+pub fn write_confirmation(
+    confirmation: &Confirmation,
+    buf: &mut [u8],
+) -> Result<usize, SixtopError> {
+    buf.pwrite(confirmation, 0)
 }
 
-#[cfg(test)]
+pub fn write_count_response(
+    response: &CountResponse,
+    buf: &mut [u8],
+) -> Result<usize, SixtopError> {
+    buf.pwrite(response, 0)
+}
+
+pub fn write_signal_response(
+    response: &SignalResponse,
+    buf: &mut [u8],
+) -> Result<usize, SixtopError> {
+    buf.pwrite(response, 0)
+}
+
+/// Write `msg` into `buf`.
+///
+/// returns the number of bytes written,
+///         Err if `buf` is too small for `msg`; MAX_MSG_SZ_BYTES is always enough
+pub fn write_message(msg: &SixtopMsg, buf: &mut [u8]) -> Result<usize, SixtopError> {
+    buf.pwrite(msg, 0)
+}
+
+#[cfg(feature = "std")]
+pub fn serialize_request(request: Request) -> Result<Vec<u8>, SixtopError> {
+    serialize_message(SixtopMsg::RequestMsg(request))
+}
+
+#[cfg(feature = "std")]
+pub fn serialize_response(response: Response) -> Result<Vec<u8>, SixtopError> {
+    serialize_message(SixtopMsg::ResponseMsg(response))
+}
+
+#[cfg(feature = "std")]
+pub fn serialize_confirmation(confirmation: Confirmation) -> Result<Vec<u8>, SixtopError> {
+    serialize_message(SixtopMsg::ConfirmationMsg(confirmation))
+}
+
+#[cfg(feature = "std")]
+pub fn serialize_count_response(response: CountResponse) -> Result<Vec<u8>, SixtopError> {
+    serialize_message(SixtopMsg::CountResponseMsg(response))
+}
+
+#[cfg(feature = "std")]
+pub fn serialize_signal_response(response: SignalResponse) -> Result<Vec<u8>, SixtopError> {
+    serialize_message(SixtopMsg::SignalResponseMsg(response))
+}
+
+#[cfg(feature = "std")]
+pub fn serialize_message(msg: SixtopMsg) -> Result<Vec<u8>, SixtopError> {
+    let mut buf = [0; MAX_MSG_SZ_BYTES];
+    let len = write_message(&msg, &mut buf)?;
+    Ok(buf[..len].to_vec())
+}
+
+#[cfg(all(test, feature = "std"))]
 mod tests {
     use super::*;
-    use crate::types::{Cell, Msg, MsgType, RequestType, ReturnCode, DEFAULT_SFID};
+    use crate::types::{CellList, CellOptions, Msg, MsgType, Payload, ReturnCode, DEFAULT_SFID};
 
     const TEST_SEQNUM: u8 = 4;
     const TEST_METADATA: u16 = 0b1111_1111_0000_0000;
@@ -47,7 +255,7 @@ mod tests {
     #[test]
     fn test_serialize_request_header() {
         let mut test_hdr = MsgHdr::new(MsgType::REQUEST);
-        test_hdr.code = RequestType::ADD as u8;
+        test_hdr.code = RequestType::ADD;
         test_hdr.seqnum = TEST_SEQNUM;
 
         // RUN TEST
@@ -58,7 +266,7 @@ mod tests {
             result.as_slice(),
             [
                 0b0000_0000,
-                RequestType::ADD as u8,
+                RequestType::ADD.to_u8(),
                 DEFAULT_SFID,
                 TEST_SEQNUM
             ]
@@ -68,7 +276,7 @@ mod tests {
     #[test]
     fn test_serialize_response_header() {
         let mut test_hdr = MsgHdr::new(MsgType::RESPONSE);
-        test_hdr.code = ReturnCode::RC_ERR as u8;
+        test_hdr.code = ReturnCode::RC_ERR;
         test_hdr.seqnum = TEST_SEQNUM;
 
         // RUN TEST
@@ -79,7 +287,7 @@ mod tests {
             result.as_slice(),
             [
                 0b0000_0100,
-                ReturnCode::RC_ERR as u8,
+                ReturnCode::RC_ERR.to_u8(),
                 DEFAULT_SFID,
                 TEST_SEQNUM
             ]
@@ -89,20 +297,26 @@ mod tests {
     #[test]
     fn test_serialize_request() {
         let mut test_request = Request::new();
-        test_request.header.code = RequestType::ADD as u8;
+        test_request.header.code = RequestType::ADD;
         test_request.header.seqnum = TEST_SEQNUM;
 
         test_request.metadata = TEST_METADATA;
-        test_request.cell_options = 0b100;
-        test_request.num_cells = 3;
-        test_request.cell_list.push(Cell {
-            slot_offset: 1,
-            channel_offset: 2,
-        });
-        test_request.cell_list.push(Cell {
-            slot_offset: 3,
-            channel_offset: 9,
-        });
+        test_request.cell_options = CellOptions::SHARED;
+        test_request.num_cells = 2;
+        test_request
+            .cell_list
+            .push(Cell {
+                slot_offset: 1,
+                channel_offset: 2,
+            })
+            .unwrap();
+        test_request
+            .cell_list
+            .push(Cell {
+                slot_offset: 3,
+                channel_offset: 9,
+            })
+            .unwrap();
 
         // RUN TEST
         let result = serialize_request(test_request).unwrap();
@@ -112,13 +326,95 @@ mod tests {
             result.as_slice(),
             [
                 0b0000_0000,
-                RequestType::ADD as u8,
+                RequestType::ADD.to_u8(),
                 DEFAULT_SFID,
                 TEST_SEQNUM,
                 0b0000_0000,
                 0b1111_1111,
                 0b0000_0100,
+                2,
+                1,
+                0,
+                2,
+                0,
                 3,
+                0,
+                9,
+                0
+            ]
+        );
+    }
+
+    #[test]
+    fn test_serialize_request_num_cells_mismatch() {
+        let mut test_request = Request::new();
+        test_request.header.code = RequestType::DELETE;
+        test_request.num_cells = 2;
+        test_request
+            .cell_list
+            .push(Cell {
+                slot_offset: 1,
+                channel_offset: 2,
+            })
+            .unwrap();
+
+        // RUN TEST
+        let result = write_request(&test_request, &mut [0; MAX_MSG_SZ_BYTES]);
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result, Err(SixtopError::NumCellsMismatch));
+    }
+
+    #[test]
+    fn test_serialize_relocate_request() {
+        let mut test_request = Request::new();
+        test_request.header.code = RequestType::RELOCATE;
+        test_request.header.seqnum = TEST_SEQNUM;
+
+        test_request.metadata = TEST_METADATA;
+        test_request.cell_options = CellOptions::SHARED;
+        test_request.num_cells = 1;
+        test_request.relocation_cell_list = Some(
+            CellList::from_slice(&[Cell {
+                slot_offset: 7,
+                channel_offset: 1,
+            }])
+            .unwrap(),
+        );
+        test_request
+            .cell_list
+            .push(Cell {
+                slot_offset: 1,
+                channel_offset: 2,
+            })
+            .unwrap();
+        test_request
+            .cell_list
+            .push(Cell {
+                slot_offset: 3,
+                channel_offset: 9,
+            })
+            .unwrap();
+
+        // RUN TEST
+        let result = serialize_request(test_request).unwrap();
+
+        // ASSERT POSTCONDITION
+        assert_eq!(
+            result.as_slice(),
+            [
+                0b0000_0000,
+                RequestType::RELOCATE.to_u8(),
+                DEFAULT_SFID,
+                TEST_SEQNUM,
+                0b0000_0000,
+                0b1111_1111,
+                0b0000_0100,
+                1,
+                7,
+                0,
+                1,
+                0,
                 1,
                 0,
                 2,
@@ -131,20 +427,185 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_serialize_count_request() {
+        let mut test_request = Request::new();
+        test_request.header.code = RequestType::COUNT;
+        test_request.header.seqnum = TEST_SEQNUM;
+        test_request.metadata = TEST_METADATA;
+        test_request.cell_options = CellOptions::SHARED;
+
+        // RUN TEST
+        let result = serialize_request(test_request).unwrap();
+
+        // ASSERT POSTCONDITION
+        assert_eq!(
+            result.as_slice(),
+            [
+                0b0000_0000,
+                RequestType::COUNT.to_u8(),
+                DEFAULT_SFID,
+                TEST_SEQNUM,
+                0b0000_0000,
+                0b1111_1111,
+                0b0000_0100
+            ]
+        );
+    }
+
+    #[test]
+    fn test_serialize_list_request() {
+        let mut test_request = Request::new();
+        test_request.header.code = RequestType::LIST;
+        test_request.header.seqnum = TEST_SEQNUM;
+        test_request.metadata = TEST_METADATA;
+        test_request.cell_options = CellOptions::SHARED;
+        test_request.offset = 0x0102;
+        test_request.max_num_cells = 5;
+
+        // RUN TEST
+        let result = serialize_request(test_request).unwrap();
+
+        // ASSERT POSTCONDITION
+        assert_eq!(
+            result.as_slice(),
+            [
+                0b0000_0000,
+                RequestType::LIST.to_u8(),
+                DEFAULT_SFID,
+                TEST_SEQNUM,
+                0b0000_0000,
+                0b1111_1111,
+                0b0000_0100,
+                0,
+                0x02,
+                0x01,
+                5,
+                0
+            ]
+        );
+    }
+
+    #[test]
+    fn test_serialize_clear_request() {
+        let mut test_request = Request::new();
+        test_request.header.code = RequestType::CLEAR;
+        test_request.header.seqnum = TEST_SEQNUM;
+        test_request.metadata = TEST_METADATA;
+        test_request.cell_options = CellOptions::SHARED;
+
+        // RUN TEST
+        let result = serialize_request(test_request).unwrap();
+
+        // ASSERT POSTCONDITION
+        assert_eq!(
+            result.as_slice(),
+            [
+                0b0000_0000,
+                RequestType::CLEAR.to_u8(),
+                DEFAULT_SFID,
+                TEST_SEQNUM,
+                0b0000_0000,
+                0b1111_1111
+            ]
+        );
+    }
+
+    #[test]
+    fn test_serialize_signal_request() {
+        let mut test_request = Request::new();
+        test_request.header.code = RequestType::SIGNAl;
+        test_request.header.seqnum = TEST_SEQNUM;
+        test_request.metadata = TEST_METADATA;
+        test_request.payload = Payload::from_slice(&[0xCA, 0xFE]).unwrap();
+
+        // RUN TEST
+        let result = serialize_request(test_request).unwrap();
+
+        // ASSERT POSTCONDITION
+        assert_eq!(
+            result.as_slice(),
+            [
+                0b0000_0000,
+                RequestType::SIGNAl.to_u8(),
+                DEFAULT_SFID,
+                TEST_SEQNUM,
+                0b0000_0000,
+                0b1111_1111,
+                0xCA,
+                0xFE
+            ]
+        );
+    }
+
+    #[test]
+    fn test_serialize_signal_response() {
+        let mut test_response = SignalResponse::new();
+        test_response.header.code = ReturnCode::RC_SUCCESS;
+        test_response.header.seqnum = TEST_SEQNUM;
+        test_response.payload = Payload::from_slice(&[0xBE, 0xEF]).unwrap();
+
+        // RUN TEST
+        let result = serialize_signal_response(test_response).unwrap();
+
+        // ASSERT POSTCONDITION
+        assert_eq!(
+            result.as_slice(),
+            [
+                0b0000_0100,
+                ReturnCode::RC_SUCCESS.to_u8(),
+                DEFAULT_SFID,
+                TEST_SEQNUM,
+                0xBE,
+                0xEF
+            ]
+        );
+    }
+
+    #[test]
+    fn test_serialize_count_response() {
+        let mut test_response = CountResponse::new();
+        test_response.header.code = ReturnCode::RC_SUCCESS;
+        test_response.header.seqnum = TEST_SEQNUM;
+        test_response.num_cells = 0x0102;
+
+        // RUN TEST
+        let result = serialize_count_response(test_response).unwrap();
+
+        // ASSERT POSTCONDITION
+        assert_eq!(
+            result.as_slice(),
+            [
+                0b0000_0100,
+                ReturnCode::RC_SUCCESS.to_u8(),
+                DEFAULT_SFID,
+                TEST_SEQNUM,
+                0x02,
+                0x01
+            ]
+        );
+    }
+
     #[test]
     fn test_serialize_response() {
         let mut test_response = Response::new();
-        test_response.header.code = ReturnCode::RC_ERR_SEQNUM as u8;
+        test_response.header.code = ReturnCode::RC_ERR_SEQNUM;
         test_response.header.seqnum = TEST_SEQNUM;
 
-        test_response.cell_list.push(Cell {
-            slot_offset: 2,
-            channel_offset: 3,
-        });
-        test_response.cell_list.push(Cell {
-            slot_offset: 4,
-            channel_offset: 5,
-        });
+        test_response
+            .cell_list
+            .push(Cell {
+                slot_offset: 2,
+                channel_offset: 3,
+            })
+            .unwrap();
+        test_response
+            .cell_list
+            .push(Cell {
+                slot_offset: 4,
+                channel_offset: 5,
+            })
+            .unwrap();
 
         // RUN TEST
         let result = serialize_response(test_response).unwrap();
@@ -154,7 +615,7 @@ mod tests {
             result.as_slice(),
             [
                 0b0000_0100,
-                ReturnCode::RC_ERR_SEQNUM as u8,
+                ReturnCode::RC_ERR_SEQNUM.to_u8(),
                 DEFAULT_SFID,
                 TEST_SEQNUM,
                 2,
@@ -168,4 +629,132 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_serialize_confirmation() {
+        let mut test_confirmation = Confirmation::new();
+        test_confirmation.header.code = ReturnCode::RC_SUCCESS;
+        test_confirmation.header.seqnum = TEST_SEQNUM;
+
+        test_confirmation
+            .cell_list
+            .push(Cell {
+                slot_offset: 2,
+                channel_offset: 3,
+            })
+            .unwrap();
+
+        // RUN TEST
+        let result = serialize_confirmation(test_confirmation).unwrap();
+
+        // ASSERT POSTCONDITION
+        assert_eq!(
+            result.as_slice(),
+            [
+                0b0000_1000,
+                ReturnCode::RC_SUCCESS.to_u8(),
+                DEFAULT_SFID,
+                TEST_SEQNUM,
+                2,
+                0,
+                3,
+                0
+            ]
+        );
+    }
+
+    #[test]
+    fn test_write_request_buffer_too_small() {
+        let mut test_request = Request::new();
+        test_request.header.code = RequestType::COUNT;
+        let mut buf = [0; SIXTOP_HDR_SZ_BYTES + 2];
+
+        // RUN TEST
+        let result = write_request(&test_request, &mut buf);
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result, Err(SixtopError::BufferTooSmall));
+    }
+
+    #[test]
+    fn test_write_message() {
+        let mut test_response = CountResponse::new();
+        test_response.header.seqnum = TEST_SEQNUM;
+        test_response.num_cells = 0x0102;
+        let mut buf = [0xFF; MAX_MSG_SZ_BYTES];
+
+        // RUN TEST
+        let result = write_message(&SixtopMsg::CountResponseMsg(test_response), &mut buf);
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result, Ok(6));
+        assert_eq!(
+            buf[..6],
+            [0b0000_0100, 0, DEFAULT_SFID, TEST_SEQNUM, 0x02, 0x01]
+        );
+    }
+
+    #[test]
+    fn test_pwrite_request_at_offset() {
+        let mut test_request = Request::new();
+        test_request.header.code = RequestType::CLEAR;
+        test_request.header.seqnum = TEST_SEQNUM;
+        test_request.metadata = TEST_METADATA;
+        // e.g. a MAC header and the IE header in front of the 6P message
+        let mut frame = [0xFF; 12];
+
+        // RUN TEST
+        let result = frame.pwrite(&test_request, 5);
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result, Ok(6));
+        assert_eq!(
+            frame,
+            [
+                0xFF,
+                0xFF,
+                0xFF,
+                0xFF,
+                0xFF,
+                0b0000_0000,
+                RequestType::CLEAR.to_u8(),
+                DEFAULT_SFID,
+                TEST_SEQNUM,
+                0b0000_0000,
+                0b1111_1111,
+                0xFF
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pwrite_response_too_small() {
+        let mut test_response = Response::new();
+        test_response
+            .cell_list
+            .push(Cell {
+                slot_offset: 2,
+                channel_offset: 3,
+            })
+            .unwrap();
+        let mut frame = [0; 10];
+
+        // RUN TEST
+        let result = frame.pwrite(&test_response, 3);
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result, Err(SixtopError::BufferTooSmall));
+    }
+
+    #[test]
+    fn test_write_empty_signal_response() {
+        let test_response = SignalResponse::new();
+        let mut buf = [0; SIXTOP_HDR_SZ_BYTES];
+
+        // RUN TEST
+        let result = write_signal_response(&test_response, &mut buf);
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result, Ok(SIXTOP_HDR_SZ_BYTES));
+    }
 }
@@ -1,83 +1,323 @@
-use std::convert::TryInto;
-use std::vec::Vec;
+/**
+ * Everything in here parses frames straight from the radio, so no input may make it panic:
+ * every access is bounds-checked and malformed messages are reported as a SixtopError.
+ *
+ * parse_message() and parse_response() return views that borrow from the input;
+ * deserialize_message() and deserialize_response() copy them into owned messages. Without std,
+ * convert the views with SixtopMsg::try_from() instead.
+ *
+ * MsgHdr, Cell, Request and Response implement scroll's TryFromCtx, so they can also be read
+ * with Pread from any offset of a frame, e.g. `frame.pread::<Request>(offset)`. A Request or
+ * Response extends to the end of the buffer.
+ */
+use core::convert::TryInto;
 
+use scroll::ctx::TryFromCtx;
+use scroll::{Pread, LE};
+
+use crate::error::SixtopError;
+use crate::msg_ref::{
+    CellListRef, ConfirmationRef, RequestRef, ResponseRef, SignalResponseRef, SixtopMsgRef,
+    CELL_SZ_BYTES,
+};
+#[cfg(feature = "std")]
+use crate::types::SixtopMsg;
 use crate::types::{
-    Cell, CellList, Msg, MsgHdr, MsgType, Request, Response, SixtopMsg, PREAMBLE_TYPE_MASK,
+    Cell, CellOptions, CountResponse, Msg, MsgHdr, MsgType, Request, RequestType, Response,
+    ReturnCode, PREAMBLE_RESERVED_MASK, PREAMBLE_TYPE_MASK, PREAMBLE_VERSION_MASK,
+    SIXTOP_HDR_SZ_BYTES, SIXTOP_VERSION,
 };
 
-const SIXTOP_HDR_SZ_BYTES: usize = 4;
+/// Metadata (2 bytes), CellOptions (1 byte), NumCells (1 byte)
+const CELL_REQUEST_BODY_SZ_BYTES: usize = 4;
+/// Metadata (2 bytes), CellOptions (1 byte), Reserved (1 byte), Offset (2 bytes),
+/// MaxNumCells (2 bytes)
+const LIST_REQUEST_BODY_SZ_BYTES: usize = 8;
+/// Metadata (2 bytes), CellOptions (1 byte)
+const COUNT_REQUEST_BODY_SZ_BYTES: usize = 3;
+const METADATA_SZ_BYTES: usize = 2;
+
+/// Read the little-endian u16 at `position` of `data`.
+fn read_u16(data: &[u8], position: usize) -> Option<u16> {
+    data.pread_with::<u16>(position, LE).ok()
+}
 
-fn deserialize_cell_list(data: Vec<u8>) -> Result<CellList, ()> {
-    // TODO: make this somehow less.. awful
-    let mut cell_list = CellList::new();
-    for (position, _) in data.iter().enumerate() {
-        if (position % 4) == 0 {
-            let this_elem = data.get(position..position + 2).unwrap();
-            let next_elem = data.get(position + 2..position + 4).unwrap();
-            let cell = Cell {
-                slot_offset: u16::from_le_bytes(this_elem.try_into().unwrap()),
-                channel_offset: u16::from_le_bytes(next_elem.try_into().unwrap()),
-            };
-            cell_list.push(cell);
-        } // otherwise this is data we've already read in the prev step; skipping
+/// Check that `data` is exactly `size` bytes long.
+fn expect_len(data: &[u8], size: usize) -> Result<(), SixtopError> {
+    if data.len() < size {
+        return Err(SixtopError::TruncatedBody);
     }
+    if data.len() > size {
+        return Err(SixtopError::TrailingBytes);
+    }
+    Ok(())
+}
 
-    Ok(cell_list)
+fn parse_cell_list(data: &[u8]) -> Result<CellListRef<'_>, SixtopError> {
+    CellListRef::new(data).ok_or(SixtopError::TruncatedCellList)
 }
 
-fn deserialize_request_body(mut data: Vec<u8>) -> Result<Request, ()> {
-    let mut request = Request::new();
+fn parse_request_body(header: MsgHdr, data: &[u8]) -> Result<RequestRef<'_>, SixtopError> {
+    let code = RequestType::from_wire(header.code);
+    let mut request = RequestRef {
+        header: header.with_code(code),
+        metadata: 0,
+        cell_options: CellOptions::NONE,
+        num_cells: 0,
+        cell_list: parse_cell_list(&[])?,
+        relocation_cell_list: None,
+        offset: 0,
+        max_num_cells: 0,
+        payload: &[],
+    };
+
+    if header.version != SIXTOP_VERSION {
+        // the body may look different in other versions, the request gets an RC_ERR_VERSION
+        return Ok(request);
+    }
+    if let RequestType::Reserved | RequestType::Unassigned(_) = code {
+        // we don't know the format of the body, but the request still gets an RC_ERR. The
+        // Metadata is kept for the SF if the body is long enough to have one.
+        request.metadata = read_u16(data, 0).unwrap_or(0);
+        return Ok(request);
+    }
+    request.metadata = read_u16(data, 0).ok_or(SixtopError::TruncatedBody)?;
+
+    match code {
+        RequestType::CLEAR => {
+            // CLEAR requests only carry the Metadata
+            expect_len(data, METADATA_SZ_BYTES)?;
+            return Ok(request);
+        }
+        RequestType::SIGNAl => {
+            request.payload = &data[METADATA_SZ_BYTES..];
+            return Ok(request);
+        }
+        _ => {}
+    }
+
+    request.cell_options = CellOptions::from_u8(*data.get(2).ok_or(SixtopError::TruncatedBody)?)?;
+
+    match code {
+        RequestType::COUNT => {
+            // COUNT requests end after the CellOptions
+            expect_len(data, COUNT_REQUEST_BODY_SZ_BYTES)?;
+            return Ok(request);
+        }
+        RequestType::LIST => {
+            // skip the Reserved byte
+            expect_len(data, LIST_REQUEST_BODY_SZ_BYTES)?;
+            request.offset = read_u16(data, 4).ok_or(SixtopError::TruncatedBody)?;
+            request.max_num_cells = read_u16(data, 6).ok_or(SixtopError::TruncatedBody)?;
+            return Ok(request);
+        }
+        _ => {}
+    }
+
+    request.num_cells = *data.get(3).ok_or(SixtopError::TruncatedBody)?;
 
-    let metadata = data.get(0..2).unwrap();
-    request.metadata = u16::from_le_bytes(metadata.try_into().unwrap());
-    request.cell_options = *data.get(2).unwrap();
-    request.num_cells = *data.get(3).unwrap();
+    let mut cell_list_data = &data[CELL_REQUEST_BODY_SZ_BYTES..];
+    if cell_list_data.len() % CELL_SZ_BYTES != 0 {
+        return Err(SixtopError::TruncatedCellList);
+    }
 
-    let previous_data_sz = 4;
-    request.cell_list = deserialize_cell_list(data.split_off(previous_data_sz)).unwrap();
+    if let RequestType::RELOCATE = code {
+        // the RelocationCellList contains exactly NumCells cells, the CandidateCellList the rest
+        let relocation_list_sz = request.num_cells as usize * CELL_SZ_BYTES;
+        if cell_list_data.len() < relocation_list_sz {
+            return Err(SixtopError::NumCellsMismatch);
+        }
+        let (relocation_cell_list_data, candidate_cell_list_data) =
+            cell_list_data.split_at(relocation_list_sz);
+        request.relocation_cell_list = Some(parse_cell_list(relocation_cell_list_data)?);
+        cell_list_data = candidate_cell_list_data;
+    }
+    request.cell_list = parse_cell_list(cell_list_data)?;
+
+    // an empty CellList asks the responder for candidates (3-step transaction), otherwise it has
+    // to hold at least NumCells cells to add or delete
+    if let RequestType::ADD | RequestType::DELETE = code {
+        if !request.cell_list.is_empty() && request.cell_list.len() < request.num_cells as usize {
+            return Err(SixtopError::NumCellsMismatch);
+        }
+    }
 
     Ok(request)
 }
 
-fn deserialize_header(data: Vec<u8>) -> Result<MsgHdr, ()> {
+/// Responses and confirmations of other versions may look different, so we can't read them.
+fn expect_version(header: &MsgHdr) -> Result<(), SixtopError> {
+    if header.version != SIXTOP_VERSION {
+        return Err(SixtopError::UnsupportedVersion(header.version));
+    }
+    Ok(())
+}
+
+fn deserialize_header(data: &[u8]) -> Result<MsgHdr, SixtopError> {
     let mut header = MsgHdr::new(MsgType::Unassigned);
-    // todo coherence check for: data length, preamble (version, reserved)...
+    if data.len() < SIXTOP_HDR_SZ_BYTES {
+        return Err(SixtopError::TruncatedHeader);
+    }
 
-    let preamble = *data.get(0).unwrap();
-    header.msg_type = MsgType::from_u8((PREAMBLE_TYPE_MASK & preamble) >> 2).unwrap();
-    header.code = *data.get(1).unwrap(); // todo coherence check?
-    header.sfid = *data.get(2).unwrap();
-    header.seqnum = *data.get(3).unwrap();
+    let preamble = data[0];
+    header.version = (PREAMBLE_VERSION_MASK & preamble) >> 4;
+    header.reserved = PREAMBLE_RESERVED_MASK & preamble;
+    header.msg_type = MsgType::from_u8((PREAMBLE_TYPE_MASK & preamble) >> 2)?;
+    header.code = data[1]; // todo coherence check?
+    header.sfid = data[2];
+    header.seqnum = data[3];
 
     Ok(header)
 }
 
-pub fn deserialize_message(mut data: Vec<u8>) -> Result<SixtopMsg, ()> {
-    let payload = data.split_off(SIXTOP_HDR_SZ_BYTES);
-    let msg_hdr = deserialize_header(data).unwrap();
-    match msg_hdr.msg_type {
-        MsgType::REQUEST => {
-            let mut request = deserialize_request_body(payload).unwrap();
-            request.header = msg_hdr;
-            Ok(SixtopMsg::RequestMsg(request))
+fn parse_response_body(
+    header: MsgHdr,
+    data: &[u8],
+    request_type: RequestType,
+) -> Result<SixtopMsgRef<'_>, SixtopError> {
+    expect_version(&header)?;
+    let header = header.with_code(ReturnCode::from_wire(header.code));
+    match request_type {
+        RequestType::COUNT => {
+            let mut response = CountResponse::new();
+            response.header = header;
+            // error responses may come without a NumCells field
+            if !data.is_empty() {
+                expect_len(data, 2)?;
+                response.num_cells = read_u16(data, 0).ok_or(SixtopError::TruncatedBody)?;
+            }
+            Ok(SixtopMsgRef::CountResponseMsg(response))
         }
-        MsgType::RESPONSE => {
-            // note: response body only contains a CellList, no need for a dedicated
-            // deserialize_response_body()
-            let mut response = Response::new();
-            response.header = msg_hdr;
-            response.cell_list = deserialize_cell_list(payload).unwrap();
+        RequestType::SIGNAl => Ok(SixtopMsgRef::SignalResponseMsg(SignalResponseRef {
+            header,
+            payload: data,
+        })),
+        _ => Ok(SixtopMsgRef::ResponseMsg(ResponseRef {
+            header,
+            cell_list: parse_cell_list(data)?,
+        })),
+    }
+}
+
+/// Split `data` into the 6P header and the body of the message.
+fn split_header(data: &[u8]) -> Result<(MsgHdr, &[u8]), SixtopError> {
+    if data.len() < SIXTOP_HDR_SZ_BYTES {
+        return Err(SixtopError::TruncatedHeader);
+    }
+    let (header, body) = data.split_at(SIXTOP_HDR_SZ_BYTES);
+    Ok((deserialize_header(header)?, body))
+}
+
+impl<'a> TryFromCtx<'a> for MsgHdr {
+    type Error = SixtopError;
+
+    fn try_from_ctx(src: &'a [u8], _: ()) -> Result<(MsgHdr, usize), SixtopError> {
+        Ok((deserialize_header(src)?, SIXTOP_HDR_SZ_BYTES))
+    }
+}
+
+impl<'a> TryFromCtx<'a> for Cell {
+    type Error = SixtopError;
+
+    fn try_from_ctx(src: &'a [u8], _: ()) -> Result<(Cell, usize), SixtopError> {
+        let cell = Cell {
+            slot_offset: read_u16(src, 0).ok_or(SixtopError::TruncatedCellList)?,
+            channel_offset: read_u16(src, 2).ok_or(SixtopError::TruncatedCellList)?,
+        };
+        Ok((cell, CELL_SZ_BYTES))
+    }
+}
+
+impl<'a> TryFromCtx<'a> for Request {
+    type Error = SixtopError;
+
+    fn try_from_ctx(src: &'a [u8], _: ()) -> Result<(Request, usize), SixtopError> {
+        let (header, body) = split_header(src)?;
+        if header.msg_type != MsgType::REQUEST {
+            return Err(SixtopError::UnexpectedMsgType);
+        }
+        Ok((parse_request_body(header, body)?.try_into()?, src.len()))
+    }
+}
+
+/// Reads responses that carry a CellList, i.e. answers to everything but COUNT and SIGNAL
+/// requests.
+impl<'a> TryFromCtx<'a> for Response {
+    type Error = SixtopError;
 
-            Ok(SixtopMsg::ResponseMsg(response))
+    fn try_from_ctx(src: &'a [u8], _: ()) -> Result<(Response, usize), SixtopError> {
+        let (header, body) = split_header(src)?;
+        if header.msg_type != MsgType::RESPONSE {
+            return Err(SixtopError::UnexpectedMsgType);
         }
-        _ => unimplemented!(),
+        expect_version(&header)?;
+        let response = Response {
+            header: header.with_code(ReturnCode::from_wire(header.code)),
+            cell_list: parse_cell_list(body)?.to_cell_list()?,
+        };
+        Ok((response, src.len()))
     }
 }
 
-#[cfg(test)]
+/// Parse a 6P message without copying its cell lists or payload out of `data`.
+///
+/// The format of a response body depends on the type of the request it answers, which isn't part
+/// of the response itself. Responses are therefore parsed as if they carried a CellList; use
+/// parse_response() if the type of the request is known, or Sixtop::parse_msg(), which knows
+/// which request a neighbor answers.
+pub fn parse_message(data: &[u8]) -> Result<SixtopMsgRef<'_>, SixtopError> {
+    let (msg_hdr, payload) = split_header(data)?;
+    match msg_hdr.msg_type {
+        MsgType::REQUEST => Ok(SixtopMsgRef::RequestMsg(parse_request_body(
+            msg_hdr, payload,
+        )?)),
+        MsgType::RESPONSE => parse_response_body(msg_hdr, payload, RequestType::ADD),
+        MsgType::CONFIRMATION => {
+            expect_version(&msg_hdr)?;
+            Ok(SixtopMsgRef::ConfirmationMsg(ConfirmationRef {
+                header: msg_hdr.with_code(ReturnCode::from_wire(msg_hdr.code)),
+                cell_list: parse_cell_list(payload)?,
+            }))
+        }
+        MsgType::Unassigned => Err(SixtopError::UnknownMsgType(MsgType::Unassigned as u8)),
+    }
+}
+
+/// Parse a 6P Response to a request of type `request_type` without copying its cell list or
+/// payload out of `data`.
+pub fn parse_response(
+    data: &[u8],
+    request_type: RequestType,
+) -> Result<SixtopMsgRef<'_>, SixtopError> {
+    let (msg_hdr, payload) = split_header(data)?;
+    match msg_hdr.msg_type {
+        MsgType::RESPONSE => parse_response_body(msg_hdr, payload, request_type),
+        _ => Err(SixtopError::UnexpectedMsgType),
+    }
+}
+
+/// Parse a 6P message, see parse_message().
+#[cfg(feature = "std")]
+pub fn deserialize_message(data: Vec<u8>) -> Result<SixtopMsg, SixtopError> {
+    parse_message(&data)?.try_into()
+}
+
+/// Parse a 6P Response to a request of type `request_type`.
+#[cfg(feature = "std")]
+pub fn deserialize_response(
+    data: Vec<u8>,
+    request_type: RequestType,
+) -> Result<SixtopMsg, SixtopError> {
+    parse_response(&data, request_type)?.try_into()
+}
+
+#[cfg(all(test, feature = "std"))]
 mod tests {
     use super::*;
-    use crate::types::{Request, RequestType, Response, ReturnCode, SixtopMsg, DEFAULT_SFID};
+    use crate::types::{
+        Cell, CellList, Confirmation, Payload, Request, Response, ReturnCode, SignalResponse,
+        DEFAULT_SFID,
+    };
 
     const TEST_SEQNUM: u8 = 4;
     const TEST_METADATA: u16 = 0b1111_1111_0000_0000;
@@ -86,30 +326,98 @@ mod tests {
     fn test_deserialize_response_header() {
         let test_hdr = vec![
             0b0000_0100,
-            ReturnCode::RC_ERR as u8,
+            ReturnCode::RC_ERR.to_u8(),
             DEFAULT_SFID,
             TEST_SEQNUM,
         ];
 
         let mut ref_msg_hdr = MsgHdr::new(MsgType::RESPONSE);
-        ref_msg_hdr.code = ReturnCode::RC_ERR as u8;
+        ref_msg_hdr.code = ReturnCode::RC_ERR.to_u8();
         ref_msg_hdr.seqnum = TEST_SEQNUM;
 
-        let result = deserialize_header(test_hdr).unwrap();
+        let result = deserialize_header(&test_hdr).unwrap();
         assert_eq!(result, ref_msg_hdr);
     }
 
+    #[test]
+    fn test_deserialize_header_version_and_reserved() {
+        let test_hdr = [
+            0b0001_0111,
+            RequestType::ADD.to_u8(),
+            DEFAULT_SFID,
+            TEST_SEQNUM,
+        ];
+
+        // RUN TEST
+        let result = deserialize_header(&test_hdr).unwrap();
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result.version, 1);
+        assert_eq!(result.reserved, 0b11);
+        assert_eq!(result.msg_type, MsgType::RESPONSE);
+        assert_eq!(result.to_bytes(), test_hdr);
+    }
+
+    #[test]
+    fn test_deserialize_request_unsupported_version() {
+        // the body doesn't make sense in version 0
+        let test_msg = vec![
+            0b0001_0000,
+            RequestType::ADD.to_u8(),
+            DEFAULT_SFID,
+            TEST_SEQNUM,
+            1,
+        ];
+
+        // RUN TEST
+        let result = deserialize_message(test_msg).unwrap();
+
+        // ASSERT POSTCONDITION
+        if let SixtopMsg::RequestMsg(request) = result {
+            assert_eq!(request.header.version, 1);
+            assert_eq!(request.header.code, RequestType::ADD);
+            assert_eq!(request.header.seqnum, TEST_SEQNUM);
+        } else {
+            panic!("expected a request");
+        }
+    }
+
+    #[test]
+    fn test_deserialize_response_unsupported_version() {
+        let test_msg = vec![
+            0b0010_0100,
+            ReturnCode::RC_SUCCESS.to_u8(),
+            DEFAULT_SFID,
+            TEST_SEQNUM,
+        ];
+
+        // RUN TEST
+        let result = deserialize_message(test_msg.clone());
+        let confirmation_result = parse_message(&[0b0010_1000, 0, DEFAULT_SFID, TEST_SEQNUM]);
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result.err(), Some(SixtopError::UnsupportedVersion(2)));
+        assert_eq!(
+            confirmation_result.err(),
+            Some(SixtopError::UnsupportedVersion(2))
+        );
+        assert_eq!(
+            test_msg.pread::<Response>(0),
+            Err(SixtopError::UnsupportedVersion(2))
+        );
+    }
+
     #[test]
     fn test_deserialize_request() {
         let test_msg = vec![
             0b0000_0000,
-            RequestType::ADD as u8,
+            RequestType::ADD.to_u8(),
             DEFAULT_SFID,
             TEST_SEQNUM,
             0b0000_0000,
             0b1111_1111,
             0b0000_0100,
-            3,
+            2,
             1,
             0,
             2,
@@ -121,20 +429,26 @@ mod tests {
         ];
 
         let mut reference_msg = Request::new();
-        reference_msg.header.code = RequestType::ADD as u8;
+        reference_msg.header.code = RequestType::ADD;
         reference_msg.header.seqnum = TEST_SEQNUM;
 
         reference_msg.metadata = TEST_METADATA;
-        reference_msg.cell_options = 0b100;
-        reference_msg.num_cells = 3;
-        reference_msg.cell_list.push(Cell {
-            slot_offset: 1,
-            channel_offset: 2,
-        });
-        reference_msg.cell_list.push(Cell {
-            slot_offset: 3,
-            channel_offset: 9,
-        });
+        reference_msg.cell_options = CellOptions::SHARED;
+        reference_msg.num_cells = 2;
+        reference_msg
+            .cell_list
+            .push(Cell {
+                slot_offset: 1,
+                channel_offset: 2,
+            })
+            .unwrap();
+        reference_msg
+            .cell_list
+            .push(Cell {
+                slot_offset: 3,
+                channel_offset: 9,
+            })
+            .unwrap();
 
         let result = deserialize_message(test_msg).unwrap();
         if let SixtopMsg::RequestMsg(request) = result {
@@ -145,11 +459,74 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_deserialize_relocate_request() {
+        let test_msg = vec![
+            0b0000_0000,
+            RequestType::RELOCATE.to_u8(),
+            DEFAULT_SFID,
+            TEST_SEQNUM,
+            0b0000_0000,
+            0b1111_1111,
+            0b0000_0100,
+            1,
+            7,
+            0,
+            1,
+            0,
+            1,
+            0,
+            2,
+            0,
+            3,
+            0,
+            9,
+            0,
+        ];
+
+        let mut reference_msg = Request::new();
+        reference_msg.header.code = RequestType::RELOCATE;
+        reference_msg.header.seqnum = TEST_SEQNUM;
+
+        reference_msg.metadata = TEST_METADATA;
+        reference_msg.cell_options = CellOptions::SHARED;
+        reference_msg.num_cells = 1;
+        reference_msg.relocation_cell_list = Some(
+            CellList::from_slice(&[Cell {
+                slot_offset: 7,
+                channel_offset: 1,
+            }])
+            .unwrap(),
+        );
+        reference_msg
+            .cell_list
+            .push(Cell {
+                slot_offset: 1,
+                channel_offset: 2,
+            })
+            .unwrap();
+        reference_msg
+            .cell_list
+            .push(Cell {
+                slot_offset: 3,
+                channel_offset: 9,
+            })
+            .unwrap();
+
+        let result = deserialize_message(test_msg).unwrap();
+        if let SixtopMsg::RequestMsg(request) = result {
+            assert_eq!(request, reference_msg);
+        } else {
+            // should have been recognized as a request
+            assert_eq!(0, 1);
+        }
+    }
+
     #[test]
     fn test_deserialize_response() {
         let test_msg = vec![
             0b0000_0100,
-            ReturnCode::RC_ERR_SEQNUM as u8,
+            ReturnCode::RC_ERR_SEQNUM.to_u8(),
             DEFAULT_SFID,
             TEST_SEQNUM,
             2,
@@ -163,17 +540,23 @@ mod tests {
         ];
 
         let mut reference_msg = Response::new();
-        reference_msg.header.code = ReturnCode::RC_ERR_SEQNUM as u8;
+        reference_msg.header.code = ReturnCode::RC_ERR_SEQNUM;
         reference_msg.header.seqnum = TEST_SEQNUM;
 
-        reference_msg.cell_list.push(Cell {
-            slot_offset: 2,
-            channel_offset: 3,
-        });
-        reference_msg.cell_list.push(Cell {
-            slot_offset: 4,
-            channel_offset: 5,
-        });
+        reference_msg
+            .cell_list
+            .push(Cell {
+                slot_offset: 2,
+                channel_offset: 3,
+            })
+            .unwrap();
+        reference_msg
+            .cell_list
+            .push(Cell {
+                slot_offset: 4,
+                channel_offset: 5,
+            })
+            .unwrap();
 
         let result = deserialize_message(test_msg).unwrap();
         if let SixtopMsg::ResponseMsg(response) = result {
@@ -184,5 +567,572 @@ mod tests {
         }
     }
 
-    // TODO add test for incomplete celllist
+    #[test]
+    fn test_deserialize_count_request() {
+        let test_msg = vec![
+            0b0000_0000,
+            RequestType::COUNT.to_u8(),
+            DEFAULT_SFID,
+            TEST_SEQNUM,
+            0b0000_0000,
+            0b1111_1111,
+            0b0000_0100,
+        ];
+
+        let mut reference_msg = Request::new();
+        reference_msg.header.code = RequestType::COUNT;
+        reference_msg.header.seqnum = TEST_SEQNUM;
+        reference_msg.metadata = TEST_METADATA;
+        reference_msg.cell_options = CellOptions::SHARED;
+
+        let result = deserialize_message(test_msg).unwrap();
+        if let SixtopMsg::RequestMsg(request) = result {
+            assert_eq!(request, reference_msg);
+        } else {
+            // should have been recognized as a request
+            assert_eq!(0, 1);
+        }
+    }
+
+    #[test]
+    fn test_deserialize_list_request() {
+        let test_msg = vec![
+            0b0000_0000,
+            RequestType::LIST.to_u8(),
+            DEFAULT_SFID,
+            TEST_SEQNUM,
+            0b0000_0000,
+            0b1111_1111,
+            0b0000_0100,
+            0,
+            0x02,
+            0x01,
+            5,
+            0,
+        ];
+
+        let mut reference_msg = Request::new();
+        reference_msg.header.code = RequestType::LIST;
+        reference_msg.header.seqnum = TEST_SEQNUM;
+        reference_msg.metadata = TEST_METADATA;
+        reference_msg.cell_options = CellOptions::SHARED;
+        reference_msg.offset = 0x0102;
+        reference_msg.max_num_cells = 5;
+
+        let result = deserialize_message(test_msg).unwrap();
+        if let SixtopMsg::RequestMsg(request) = result {
+            assert_eq!(request, reference_msg);
+        } else {
+            // should have been recognized as a request
+            assert_eq!(0, 1);
+        }
+    }
+
+    #[test]
+    fn test_deserialize_clear_request() {
+        let test_msg = vec![
+            0b0000_0000,
+            RequestType::CLEAR.to_u8(),
+            DEFAULT_SFID,
+            TEST_SEQNUM,
+            0b0000_0000,
+            0b1111_1111,
+        ];
+
+        let mut reference_msg = Request::new();
+        reference_msg.header.code = RequestType::CLEAR;
+        reference_msg.header.seqnum = TEST_SEQNUM;
+        reference_msg.metadata = TEST_METADATA;
+
+        let result = deserialize_message(test_msg).unwrap();
+        if let SixtopMsg::RequestMsg(request) = result {
+            assert_eq!(request, reference_msg);
+        } else {
+            // should have been recognized as a request
+            assert_eq!(0, 1);
+        }
+    }
+
+    #[test]
+    fn test_deserialize_unassigned_request() {
+        let test_msg = vec![
+            0b0000_0000,
+            42,
+            DEFAULT_SFID,
+            TEST_SEQNUM,
+            0b0000_0000,
+            0b1111_1111,
+            0xAB,
+        ];
+
+        let mut reference_msg = Request::new();
+        reference_msg.header.code = RequestType::Unassigned(42);
+        reference_msg.header.seqnum = TEST_SEQNUM;
+        reference_msg.metadata = TEST_METADATA;
+
+        // RUN TEST
+        let result = deserialize_message(test_msg).unwrap();
+
+        // ASSERT POSTCONDITION
+        if let SixtopMsg::RequestMsg(request) = result {
+            assert_eq!(request, reference_msg);
+        } else {
+            panic!("expected a request");
+        }
+    }
+
+    #[test]
+    fn test_deserialize_unassigned_return_code() {
+        let test_msg = vec![0b0000_0100, 42, DEFAULT_SFID, TEST_SEQNUM];
+
+        // RUN TEST
+        let result = deserialize_message(test_msg).unwrap();
+
+        // ASSERT POSTCONDITION
+        if let SixtopMsg::ResponseMsg(response) = result {
+            assert_eq!(response.header.code, ReturnCode::Unassigned(42));
+            assert_eq!(response.header.to_bytes()[1], 42);
+        } else {
+            panic!("expected a response");
+        }
+    }
+
+    #[test]
+    fn test_deserialize_signal_request() {
+        let test_msg = vec![
+            0b0000_0000,
+            RequestType::SIGNAl.to_u8(),
+            DEFAULT_SFID,
+            TEST_SEQNUM,
+            0b0000_0000,
+            0b1111_1111,
+            0xCA,
+            0xFE,
+        ];
+
+        let mut reference_msg = Request::new();
+        reference_msg.header.code = RequestType::SIGNAl;
+        reference_msg.header.seqnum = TEST_SEQNUM;
+        reference_msg.metadata = TEST_METADATA;
+        reference_msg.payload = Payload::from_slice(&[0xCA, 0xFE]).unwrap();
+
+        let result = deserialize_message(test_msg).unwrap();
+        if let SixtopMsg::RequestMsg(request) = result {
+            assert_eq!(request, reference_msg);
+        } else {
+            // should have been recognized as a request
+            assert_eq!(0, 1);
+        }
+    }
+
+    #[test]
+    fn test_deserialize_signal_response() {
+        let test_msg = vec![
+            0b0000_0100,
+            ReturnCode::RC_SUCCESS.to_u8(),
+            DEFAULT_SFID,
+            TEST_SEQNUM,
+            0xBE,
+            0xEF,
+        ];
+
+        let mut reference_msg = SignalResponse::new();
+        reference_msg.header.code = ReturnCode::RC_SUCCESS;
+        reference_msg.header.seqnum = TEST_SEQNUM;
+        reference_msg.payload = Payload::from_slice(&[0xBE, 0xEF]).unwrap();
+
+        let result = deserialize_response(test_msg, RequestType::SIGNAl).unwrap();
+        if let SixtopMsg::SignalResponseMsg(response) = result {
+            assert_eq!(response, reference_msg);
+        } else {
+            // should have been recognized as a signal response
+            assert_eq!(0, 1);
+        }
+    }
+
+    #[test]
+    fn test_deserialize_count_response() {
+        let test_msg = vec![
+            0b0000_0100,
+            ReturnCode::RC_SUCCESS.to_u8(),
+            DEFAULT_SFID,
+            TEST_SEQNUM,
+            0x02,
+            0x01,
+        ];
+
+        let mut reference_msg = CountResponse::new();
+        reference_msg.header.code = ReturnCode::RC_SUCCESS;
+        reference_msg.header.seqnum = TEST_SEQNUM;
+        reference_msg.num_cells = 0x0102;
+
+        let result = deserialize_response(test_msg, RequestType::COUNT).unwrap();
+        if let SixtopMsg::CountResponseMsg(response) = result {
+            assert_eq!(response, reference_msg);
+        } else {
+            // should have been recognized as a count response
+            assert_eq!(0, 1);
+        }
+    }
+
+    #[test]
+    fn test_deserialize_confirmation() {
+        let test_msg = vec![
+            0b0000_1000,
+            ReturnCode::RC_SUCCESS.to_u8(),
+            DEFAULT_SFID,
+            TEST_SEQNUM,
+            2,
+            0,
+            3,
+            0,
+        ];
+
+        let mut reference_msg = Confirmation::new();
+        reference_msg.header.code = ReturnCode::RC_SUCCESS;
+        reference_msg.header.seqnum = TEST_SEQNUM;
+        reference_msg
+            .cell_list
+            .push(Cell {
+                slot_offset: 2,
+                channel_offset: 3,
+            })
+            .unwrap();
+
+        let result = deserialize_message(test_msg).unwrap();
+        if let SixtopMsg::ConfirmationMsg(confirmation) = result {
+            assert_eq!(confirmation, reference_msg);
+        } else {
+            // should have been recognized as a confirmation
+            assert_eq!(0, 1);
+        }
+    }
+
+    #[test]
+    fn test_deserialize_truncated_header() {
+        let test_msg = vec![0b0000_0100, ReturnCode::RC_SUCCESS.to_u8(), DEFAULT_SFID];
+
+        let result = deserialize_message(test_msg);
+        assert_eq!(result.unwrap_err(), SixtopError::TruncatedHeader);
+    }
+
+    #[test]
+    fn test_deserialize_incomplete_cell_list() {
+        let test_msg = vec![
+            0b0000_0100,
+            ReturnCode::RC_SUCCESS.to_u8(),
+            DEFAULT_SFID,
+            TEST_SEQNUM,
+            2,
+            0,
+            3,
+        ];
+
+        let result = deserialize_message(test_msg);
+        assert_eq!(result.unwrap_err(), SixtopError::TruncatedCellList);
+    }
+
+    #[test]
+    fn test_deserialize_unknown_msg_type() {
+        let test_msg = vec![
+            0b0000_1100,
+            ReturnCode::RC_SUCCESS.to_u8(),
+            DEFAULT_SFID,
+            TEST_SEQNUM,
+        ];
+
+        let result = deserialize_message(test_msg);
+        assert_eq!(result.unwrap_err(), SixtopError::UnknownMsgType(3));
+    }
+
+    #[test]
+    fn test_deserialize_truncated_request_body() {
+        let test_msg = vec![
+            0b0000_0000,
+            RequestType::ADD.to_u8(),
+            DEFAULT_SFID,
+            TEST_SEQNUM,
+            0b0000_0000,
+            0b1111_1111,
+            0b0000_0100,
+        ];
+
+        let result = deserialize_message(test_msg);
+        assert_eq!(result.unwrap_err(), SixtopError::TruncatedBody);
+    }
+
+    #[test]
+    fn test_deserialize_request_incomplete_cell_list() {
+        let test_msg = vec![
+            0b0000_0000,
+            RequestType::ADD.to_u8(),
+            DEFAULT_SFID,
+            TEST_SEQNUM,
+            0b0000_0000,
+            0b1111_1111,
+            0b0000_0100,
+            1,
+            1,
+            0,
+            2,
+        ];
+
+        let result = deserialize_message(test_msg);
+        assert_eq!(result.unwrap_err(), SixtopError::TruncatedCellList);
+    }
+
+    #[test]
+    fn test_deserialize_relocate_request_too_few_cells() {
+        let test_msg = vec![
+            0b0000_0000,
+            RequestType::RELOCATE.to_u8(),
+            DEFAULT_SFID,
+            TEST_SEQNUM,
+            0b0000_0000,
+            0b1111_1111,
+            0b0000_0100,
+            2,
+            7,
+            0,
+            1,
+            0,
+        ];
+
+        let result = deserialize_message(test_msg);
+        assert_eq!(result.unwrap_err(), SixtopError::NumCellsMismatch);
+    }
+
+    #[test]
+    fn test_deserialize_request_too_few_cells() {
+        for code in [RequestType::ADD, RequestType::DELETE].iter() {
+            let test_msg = vec![
+                0b0000_0000,
+                code.to_u8(),
+                DEFAULT_SFID,
+                TEST_SEQNUM,
+                0b0000_0000,
+                0b1111_1111,
+                0b0000_0100,
+                2,
+                7,
+                0,
+                1,
+                0,
+            ];
+
+            // RUN TEST
+            let result = deserialize_message(test_msg);
+
+            // ASSERT POSTCONDITION
+            assert_eq!(result.unwrap_err(), SixtopError::NumCellsMismatch);
+        }
+    }
+
+    #[test]
+    fn test_deserialize_unassigned_request_short_body() {
+        let test_msg = vec![0b0000_0000, 42, DEFAULT_SFID, TEST_SEQNUM, 0xAB];
+
+        // RUN TEST
+        let result = deserialize_message(test_msg).unwrap();
+
+        // ASSERT POSTCONDITION
+        if let SixtopMsg::RequestMsg(request) = result {
+            assert_eq!(request.header.code, RequestType::Unassigned(42));
+            assert_eq!(request.metadata, 0);
+        } else {
+            panic!("expected a request");
+        }
+    }
+
+    #[test]
+    fn test_deserialize_count_request_trailing_bytes() {
+        let test_msg = vec![
+            0b0000_0000,
+            RequestType::COUNT.to_u8(),
+            DEFAULT_SFID,
+            TEST_SEQNUM,
+            0b0000_0000,
+            0b1111_1111,
+            0b0000_0100,
+            0,
+        ];
+
+        let result = deserialize_message(test_msg);
+        assert_eq!(result.unwrap_err(), SixtopError::TrailingBytes);
+    }
+
+    #[test]
+    fn test_deserialize_request_reserved_cell_options() {
+        let test_msg = vec![
+            0b0000_0000,
+            RequestType::COUNT.to_u8(),
+            DEFAULT_SFID,
+            TEST_SEQNUM,
+            0b0000_0000,
+            0b1111_1111,
+            0b0000_1001,
+        ];
+
+        let result = deserialize_message(test_msg);
+        assert_eq!(
+            result.unwrap_err(),
+            SixtopError::InvalidCellOptions(0b0000_1001)
+        );
+    }
+
+    #[test]
+    fn test_deserialize_never_panics() {
+        let request_types = [
+            RequestType::ADD,
+            RequestType::COUNT,
+            RequestType::LIST,
+            RequestType::SIGNAl,
+        ];
+        for preamble in 0..=0xFF {
+            for code in 0..=9 {
+                for len in 0..=20 {
+                    let mut test_msg = vec![preamble, code, DEFAULT_SFID, TEST_SEQNUM];
+                    test_msg.extend(0..16);
+                    // every prefix, from an empty message to 4 body bytes more than a LIST
+                    test_msg.truncate(len);
+
+                    // RUN TEST
+                    deserialize_message(test_msg.clone()).ok();
+                    for request_type in request_types.iter() {
+                        deserialize_response(test_msg.clone(), *request_type).ok();
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_relocate_request() {
+        let test_msg = vec![
+            0b0000_0000,
+            RequestType::RELOCATE.to_u8(),
+            DEFAULT_SFID,
+            TEST_SEQNUM,
+            0b0000_0000,
+            0b1111_1111,
+            0b0000_0100,
+            1,
+            7,
+            0,
+            1,
+            0,
+            1,
+            0,
+            2,
+            0,
+            3,
+            0,
+            9,
+            0,
+        ];
+
+        let result = parse_message(&test_msg).unwrap();
+        if let SixtopMsgRef::RequestMsg(request) = result {
+            assert_eq!(request.metadata, TEST_METADATA);
+            assert_eq!(request.num_cells, 1);
+            let relocation_cell_list = request.relocation_cell_list.unwrap();
+            assert_eq!(
+                relocation_cell_list.iter().next(),
+                Some(Cell {
+                    slot_offset: 7,
+                    channel_offset: 1,
+                })
+            );
+            assert_eq!(request.cell_list.len(), 2);
+            assert_eq!(
+                request.cell_list.iter().last(),
+                Some(Cell {
+                    slot_offset: 3,
+                    channel_offset: 9,
+                })
+            );
+        } else {
+            // should have been recognized as a request
+            assert_eq!(0, 1);
+        }
+    }
+
+    #[test]
+    fn test_parse_signal_response() {
+        let test_msg = vec![
+            0b0000_0100,
+            ReturnCode::RC_SUCCESS.to_u8(),
+            DEFAULT_SFID,
+            TEST_SEQNUM,
+            0xBE,
+            0xEF,
+        ];
+
+        let result = parse_response(&test_msg, RequestType::SIGNAl).unwrap();
+        if let SixtopMsgRef::SignalResponseMsg(response) = result {
+            assert_eq!(response.payload, &[0xBE, 0xEF]);
+            // the payload points into the buffer instead of being copied
+            assert_eq!(response.payload.as_ptr(), test_msg[4..].as_ptr());
+        } else {
+            // should have been recognized as a signal response
+            assert_eq!(0, 1);
+        }
+    }
+
+    #[test]
+    fn test_pread_request_at_offset() {
+        let test_frame = [
+            0xFF,
+            0xFF,
+            0xFF,
+            0b0000_0000,
+            RequestType::ADD.to_u8(),
+            DEFAULT_SFID,
+            TEST_SEQNUM,
+            0b0000_0000,
+            0b1111_1111,
+            0b0000_0100,
+            1,
+            1,
+            0,
+            2,
+            0,
+        ];
+
+        // RUN TEST
+        let result: Request = test_frame.pread(3).unwrap();
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result.header.seqnum, TEST_SEQNUM);
+        assert_eq!(result.metadata, TEST_METADATA);
+        assert_eq!(
+            result.cell_list,
+            [Cell {
+                slot_offset: 1,
+                channel_offset: 2,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_pread_request_from_response() {
+        let test_frame = [0b0000_0100, ReturnCode::RC_SUCCESS.to_u8(), DEFAULT_SFID, 0];
+
+        // RUN TEST
+        let result = test_frame.pread::<Request>(0);
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result, Err(SixtopError::UnexpectedMsgType));
+    }
+
+    #[test]
+    fn test_pread_truncated_cell() {
+        let test_frame = [1, 0, 2];
+
+        // RUN TEST
+        let result = test_frame.pread::<Cell>(0);
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result, Err(SixtopError::TruncatedCellList));
+    }
 }
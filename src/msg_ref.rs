@@ -0,0 +1,286 @@
+/**
+ * Borrowed views of 6P messages. They point into the buffer they were parsed from instead of
+ * copying cell lists and payloads, see msg_reader::parse_message(). Each of them converts into
+ * its owned counterpart from types.rs when the message has to outlive the buffer, which fails if
+ * its cell lists or payload don't fit into the fixed-capacity owned containers.
+ */
+use core::convert::{TryFrom, TryInto};
+
+use scroll::Pread;
+
+use crate::error::SixtopError;
+use crate::types::{
+    Cell, CellList, CellOptions, Confirmation, CountResponse, MsgHdr, Payload, Request, RequestHdr,
+    Response, ResponseHdr, SignalResponse, SixtopMsg,
+};
+
+pub const CELL_SZ_BYTES: usize = 4;
+
+/// A CellList as it is on the wire. Cells are only decoded when they are iterated over.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct CellListRef<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> CellListRef<'a> {
+    /// returns None if `data` ends in the middle of a cell
+    pub fn new(data: &'a [u8]) -> Option<CellListRef<'a>> {
+        if data.len() % CELL_SZ_BYTES != 0 {
+            return None;
+        }
+        Some(CellListRef { data })
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len() / CELL_SZ_BYTES
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn iter(&self) -> CellIter<'a> {
+        CellIter {
+            chunks: self.data.chunks_exact(CELL_SZ_BYTES),
+        }
+    }
+
+    /// returns Err if there are more than MAX_NUM_CELLS cells
+    pub fn to_cell_list(&self) -> Result<CellList, SixtopError> {
+        let mut cell_list = CellList::new();
+        for cell in self.iter() {
+            cell_list
+                .push(cell)
+                .map_err(|_| SixtopError::CapacityExceeded)?;
+        }
+        Ok(cell_list)
+    }
+}
+
+impl<'a> IntoIterator for CellListRef<'a> {
+    type Item = Cell;
+    type IntoIter = CellIter<'a>;
+
+    fn into_iter(self) -> CellIter<'a> {
+        self.iter()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CellIter<'a> {
+    chunks: core::slice::ChunksExact<'a, u8>,
+}
+
+impl<'a> Iterator for CellIter<'a> {
+    type Item = Cell;
+
+    fn next(&mut self) -> Option<Cell> {
+        self.chunks.next()?.pread(0).ok()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.chunks.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for CellIter<'a> {}
+
+/// Borrowed counterpart of Request
+#[derive(Debug, PartialEq, Clone)]
+pub struct RequestRef<'a> {
+    pub header: RequestHdr,
+    pub metadata: u16,
+    pub cell_options: CellOptions,
+    pub num_cells: u8,
+    pub cell_list: CellListRef<'a>,
+    pub relocation_cell_list: Option<CellListRef<'a>>,
+    pub offset: u16,
+    pub max_num_cells: u16,
+    pub payload: &'a [u8],
+}
+
+/// Borrowed counterpart of Response
+#[derive(Debug, PartialEq, Clone)]
+pub struct ResponseRef<'a> {
+    pub header: ResponseHdr,
+    pub cell_list: CellListRef<'a>,
+}
+
+/// Borrowed counterpart of Confirmation
+#[derive(Debug, PartialEq, Clone)]
+pub struct ConfirmationRef<'a> {
+    pub header: ResponseHdr,
+    pub cell_list: CellListRef<'a>,
+}
+
+/// Borrowed counterpart of SignalResponse
+#[derive(Debug, PartialEq, Clone)]
+pub struct SignalResponseRef<'a> {
+    pub header: ResponseHdr,
+    pub payload: &'a [u8],
+}
+
+/// Borrowed counterpart of SixtopMsg. COUNT responses don't borrow anything, so they are
+/// represented by the owned CountResponse.
+#[derive(Debug, PartialEq, Clone)]
+pub enum SixtopMsgRef<'a> {
+    RequestMsg(RequestRef<'a>),
+    ResponseMsg(ResponseRef<'a>),
+    CountResponseMsg(CountResponse),
+    SignalResponseMsg(SignalResponseRef<'a>),
+    ConfirmationMsg(ConfirmationRef<'a>),
+}
+
+impl<'a> SixtopMsgRef<'a> {
+    /// The header of the message, with its code as it goes on the wire.
+    pub fn header(&self) -> MsgHdr {
+        match self {
+            SixtopMsgRef::RequestMsg(request) => request.header.to_raw(),
+            SixtopMsgRef::ResponseMsg(response) => response.header.to_raw(),
+            SixtopMsgRef::CountResponseMsg(response) => response.header.to_raw(),
+            SixtopMsgRef::SignalResponseMsg(response) => response.header.to_raw(),
+            SixtopMsgRef::ConfirmationMsg(confirmation) => confirmation.header.to_raw(),
+        }
+    }
+}
+
+fn to_payload(payload: &[u8]) -> Result<Payload, SixtopError> {
+    Payload::from_slice(payload).map_err(|_| SixtopError::CapacityExceeded)
+}
+
+impl<'a> TryFrom<RequestRef<'a>> for Request {
+    type Error = SixtopError;
+
+    fn try_from(request: RequestRef<'a>) -> Result<Request, SixtopError> {
+        Ok(Request {
+            header: request.header,
+            metadata: request.metadata,
+            cell_options: request.cell_options,
+            num_cells: request.num_cells,
+            cell_list: request.cell_list.to_cell_list()?,
+            relocation_cell_list: match request.relocation_cell_list {
+                Some(cell_list) => Some(cell_list.to_cell_list()?),
+                None => None,
+            },
+            offset: request.offset,
+            max_num_cells: request.max_num_cells,
+            payload: to_payload(request.payload)?,
+        })
+    }
+}
+
+impl<'a> TryFrom<ResponseRef<'a>> for Response {
+    type Error = SixtopError;
+
+    fn try_from(response: ResponseRef<'a>) -> Result<Response, SixtopError> {
+        Ok(Response {
+            header: response.header,
+            cell_list: response.cell_list.to_cell_list()?,
+        })
+    }
+}
+
+impl<'a> TryFrom<ConfirmationRef<'a>> for Confirmation {
+    type Error = SixtopError;
+
+    fn try_from(confirmation: ConfirmationRef<'a>) -> Result<Confirmation, SixtopError> {
+        Ok(Confirmation {
+            header: confirmation.header,
+            cell_list: confirmation.cell_list.to_cell_list()?,
+        })
+    }
+}
+
+impl<'a> TryFrom<SignalResponseRef<'a>> for SignalResponse {
+    type Error = SixtopError;
+
+    fn try_from(response: SignalResponseRef<'a>) -> Result<SignalResponse, SixtopError> {
+        Ok(SignalResponse {
+            header: response.header,
+            payload: to_payload(response.payload)?,
+        })
+    }
+}
+
+impl<'a> TryFrom<SixtopMsgRef<'a>> for SixtopMsg {
+    type Error = SixtopError;
+
+    fn try_from(msg: SixtopMsgRef<'a>) -> Result<SixtopMsg, SixtopError> {
+        Ok(match msg {
+            SixtopMsgRef::RequestMsg(request) => SixtopMsg::RequestMsg(request.try_into()?),
+            SixtopMsgRef::ResponseMsg(response) => SixtopMsg::ResponseMsg(response.try_into()?),
+            SixtopMsgRef::CountResponseMsg(response) => SixtopMsg::CountResponseMsg(response),
+            SixtopMsgRef::SignalResponseMsg(response) => {
+                SixtopMsg::SignalResponseMsg(response.try_into()?)
+            }
+            SixtopMsgRef::ConfirmationMsg(confirmation) => {
+                SixtopMsg::ConfirmationMsg(confirmation.try_into()?)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{MsgType, MAX_NUM_CELLS};
+
+    const TEST_CELL_LIST: [u8; 8] = [1, 0, 2, 0, 3, 0, 9, 0];
+
+    #[test]
+    fn test_cell_list_ref() {
+        // RUN TEST
+        let result = CellListRef::new(&TEST_CELL_LIST).unwrap();
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result.len(), 2);
+        assert_eq!(
+            result.to_cell_list().unwrap(),
+            [
+                Cell {
+                    slot_offset: 1,
+                    channel_offset: 2,
+                },
+                Cell {
+                    slot_offset: 3,
+                    channel_offset: 9,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cell_list_ref_incomplete() {
+        // RUN TEST
+        let result = CellListRef::new(&TEST_CELL_LIST[..7]);
+
+        // ASSERT POSTCONDITION
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn test_response_ref_into_response() {
+        let test_response = ResponseRef {
+            header: MsgHdr::new(MsgType::RESPONSE),
+            cell_list: CellListRef::new(&TEST_CELL_LIST).unwrap(),
+        };
+
+        // RUN TEST
+        let result = Response::try_from(test_response).unwrap();
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result.cell_list.len(), 2);
+        assert_eq!(result.cell_list[1].channel_offset, 9);
+    }
+
+    #[test]
+    fn test_cell_list_ref_too_long() {
+        let test_data = [0; (MAX_NUM_CELLS + 1) * CELL_SZ_BYTES];
+
+        // RUN TEST
+        let result = CellListRef::new(&test_data).unwrap().to_cell_list();
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result, Err(SixtopError::CapacityExceeded));
+    }
+}
@@ -0,0 +1,515 @@
+#![allow(dead_code)]
+
+/**
+ * The cells we have negotiated with our neighbors, i.e. our local view of the TSCH schedule.
+ */
+use heapless::LinearMap;
+
+use crate::error::SixtopError;
+use crate::types::{Cell, CellOptions, NeighborID};
+
+/// Identifies the slotframe a cell belongs to. 6P carries it in the Metadata field of requests.
+pub type SlotframeID = u16;
+
+/// Number of cells we can schedule, over all slotframes and neighbors. The same number of cells
+/// can be locked by ongoing transactions.
+#[cfg(feature = "std")]
+pub const MAX_SCHEDULED_CELLS: usize = 512;
+#[cfg(not(feature = "std"))]
+pub const MAX_SCHEDULED_CELLS: usize = 64;
+
+/// A list of cells that can hold the whole schedule, unlike a CellList which only holds as many
+/// cells as fit into a message.
+pub type ScheduleCellList = heapless::Vec<Cell, MAX_SCHEDULED_CELLS>;
+
+type CellMap<V> = LinearMap<(SlotframeID, Cell), V, MAX_SCHEDULED_CELLS>;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct ScheduledCell {
+    pub neighbor: NeighborID,
+    pub cell_options: CellOptions,
+}
+
+#[derive(Debug, Default)]
+pub struct Schedule {
+    cells: CellMap<ScheduledCell>,
+    /// cells reserved by an ongoing transaction, and the neighbor the transaction is with
+    locks: CellMap<NeighborID>,
+}
+
+/// Remove all entries of `map` whose value matches `predicate`.
+///
+/// returns the number of removed entries
+fn remove_where<V, F>(map: &mut CellMap<V>, predicate: F) -> usize
+where
+    F: Fn(&V) -> bool,
+{
+    // can't overflow, there are at most as many keys as `map` holds
+    let keys: heapless::Vec<(SlotframeID, Cell), MAX_SCHEDULED_CELLS> = map
+        .iter()
+        .filter(|(_, value)| predicate(value))
+        .map(|(key, _)| *key)
+        .collect();
+    for key in keys.iter() {
+        map.remove(key);
+    }
+    keys.len()
+}
+
+impl Schedule {
+    pub fn new() -> Schedule {
+        Schedule {
+            ..Default::default()
+        }
+    }
+
+    /// Schedule `cell` with `neighbor`.
+    ///
+    /// returns Err if `cell` is already in use, locked by a transaction with another neighbor or
+    ///         the schedule is full
+    pub fn add_cell(
+        &mut self,
+        slotframe: SlotframeID,
+        cell: Cell,
+        neighbor: NeighborID,
+        cell_options: CellOptions,
+    ) -> Result<(), SixtopError> {
+        if self.cells.contains_key(&(slotframe, cell)) {
+            return Err(SixtopError::CellInUse);
+        }
+        if let Some(owner) = self.locked_by(slotframe, &cell) {
+            if owner != neighbor {
+                return Err(SixtopError::CellLocked);
+            }
+        }
+        self.cells
+            .insert(
+                (slotframe, cell),
+                ScheduledCell {
+                    neighbor,
+                    cell_options,
+                },
+            )
+            .map_err(|_| SixtopError::CapacityExceeded)?;
+        Ok(())
+    }
+
+    pub fn remove_cell(&mut self, slotframe: SlotframeID, cell: &Cell) -> Option<ScheduledCell> {
+        self.cells.remove(&(slotframe, *cell))
+    }
+
+    pub fn get_cell(&self, slotframe: SlotframeID, cell: &Cell) -> Option<&ScheduledCell> {
+        self.cells.get(&(slotframe, *cell))
+    }
+
+    pub fn is_scheduled(&self, slotframe: SlotframeID, cell: &Cell) -> bool {
+        self.cells.contains_key(&(slotframe, *cell))
+    }
+
+    /// Check whether `cell` is scheduled with `neighbor` using exactly `cell_options`.
+    pub fn is_scheduled_with(
+        &self,
+        slotframe: SlotframeID,
+        cell: &Cell,
+        neighbor: NeighborID,
+        cell_options: CellOptions,
+    ) -> bool {
+        match self.cells.get(&(slotframe, *cell)) {
+            Some(scheduled) => {
+                scheduled.neighbor == neighbor && scheduled.cell_options == cell_options
+            }
+            None => false,
+        }
+    }
+
+    /// Check whether `cell` is neither scheduled nor locked, i.e. whether we can offer it to a
+    /// neighbor.
+    pub fn is_available(&self, slotframe: SlotframeID, cell: &Cell) -> bool {
+        !self.is_scheduled(slotframe, cell) && !self.is_locked(slotframe, cell)
+    }
+
+    /// Remove all cells scheduled with `neighbor` in any slotframe, and release its locks.
+    ///
+    /// returns the number of removed cells
+    pub fn remove_neighbor(&mut self, neighbor: NeighborID) -> usize {
+        self.unlock_cells(neighbor);
+        remove_where(&mut self.cells, |scheduled| scheduled.neighbor == neighbor)
+    }
+
+    /// Count the cells scheduled with `neighbor` that `cell_options` select, see
+    /// CellOptions::selects().
+    pub fn count_cells(
+        &self,
+        slotframe: SlotframeID,
+        neighbor: NeighborID,
+        cell_options: CellOptions,
+    ) -> usize {
+        self.cells
+            .iter()
+            .filter(|((cell_slotframe, _), scheduled)| {
+                *cell_slotframe == slotframe
+                    && scheduled.neighbor == neighbor
+                    && cell_options.selects(scheduled.cell_options)
+            })
+            .count()
+    }
+
+    /// All cells scheduled with `neighbor` that `cell_options` select, ordered by slot offset and
+    /// channel offset so that consecutive LIST requests see the same order.
+    pub fn list_cells(
+        &self,
+        slotframe: SlotframeID,
+        neighbor: NeighborID,
+        cell_options: CellOptions,
+    ) -> ScheduleCellList {
+        let mut cell_list: ScheduleCellList = self
+            .cells
+            .iter()
+            .filter(|((cell_slotframe, _), scheduled)| {
+                *cell_slotframe == slotframe
+                    && scheduled.neighbor == neighbor
+                    && cell_options.selects(scheduled.cell_options)
+            })
+            .map(|((_, cell), _)| *cell)
+            .collect();
+        cell_list.sort_unstable_by_key(|cell| (cell.slot_offset, cell.channel_offset));
+        cell_list
+    }
+
+    /// Tentatively reserve `cell_list` for the transaction with `neighbor`, so that no other
+    /// transaction can use these cells until it is over. Either all cells are locked or none.
+    ///
+    /// returns Err if one of the cells is locked by a transaction with another neighbor or there's
+    ///         no room for more locks
+    pub fn lock_cells(
+        &mut self,
+        slotframe: SlotframeID,
+        neighbor: NeighborID,
+        cell_list: &[Cell],
+    ) -> Result<(), SixtopError> {
+        let locked_by_other = cell_list.iter().any(
+            |cell| matches!(self.locked_by(slotframe, cell), Some(owner) if owner != neighbor),
+        );
+        if locked_by_other {
+            return Err(SixtopError::CellLocked);
+        }
+
+        let num_new_locks = cell_list
+            .iter()
+            .filter(|cell| !self.is_locked(slotframe, cell))
+            .count();
+        if self.locks.len() + num_new_locks > self.locks.capacity() {
+            return Err(SixtopError::CapacityExceeded);
+        }
+
+        for cell in cell_list {
+            self.locks.insert((slotframe, *cell), neighbor).ok();
+        }
+        Ok(())
+    }
+
+    /// Release all locks held by the transaction with `neighbor`. Cells it added or removed in
+    /// the meantime stay that way, so this both commits and rolls back a transaction.
+    pub fn unlock_cells(&mut self, neighbor: NeighborID) {
+        remove_where(&mut self.locks, |owner| *owner == neighbor);
+    }
+
+    pub fn is_locked(&self, slotframe: SlotframeID, cell: &Cell) -> bool {
+        self.locks.contains_key(&(slotframe, *cell))
+    }
+
+    /// The neighbor whose transaction holds the lock on `cell`, if any.
+    pub fn locked_by(&self, slotframe: SlotframeID, cell: &Cell) -> Option<NeighborID> {
+        self.locks.get(&(slotframe, *cell)).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_NEIGHBOR: NeighborID = NeighborID::Short(22);
+    const TEST_OTHER_NEIGHBOR: NeighborID = NeighborID::Short(23);
+    const TEST_CELL_OPTIONS: CellOptions = CellOptions::TX;
+    const TEST_SLOTFRAME: SlotframeID = 0;
+    const TEST_CELL: Cell = Cell {
+        slot_offset: 4,
+        channel_offset: 2,
+    };
+
+    #[test]
+    fn test_add_cell() {
+        let mut test_schedule = Schedule::new();
+
+        // RUN TEST
+        let result =
+            test_schedule.add_cell(TEST_SLOTFRAME, TEST_CELL, TEST_NEIGHBOR, TEST_CELL_OPTIONS);
+
+        // ASSERT POSTCONDITION
+        assert!(result.is_ok());
+        assert!(test_schedule.is_scheduled_with(
+            TEST_SLOTFRAME,
+            &TEST_CELL,
+            TEST_NEIGHBOR,
+            TEST_CELL_OPTIONS
+        ));
+    }
+
+    #[test]
+    fn test_add_cell_occupied() {
+        let mut test_schedule = Schedule::new();
+        test_schedule
+            .add_cell(TEST_SLOTFRAME, TEST_CELL, TEST_NEIGHBOR, TEST_CELL_OPTIONS)
+            .unwrap();
+
+        // RUN TEST
+        let result = test_schedule.add_cell(
+            TEST_SLOTFRAME,
+            TEST_CELL,
+            TEST_OTHER_NEIGHBOR,
+            TEST_CELL_OPTIONS,
+        );
+
+        // ASSERT POSTCONDITION
+        assert!(result.is_err());
+        assert_eq!(
+            test_schedule
+                .get_cell(TEST_SLOTFRAME, &TEST_CELL)
+                .unwrap()
+                .neighbor,
+            TEST_NEIGHBOR
+        );
+    }
+
+    #[test]
+    fn test_remove_cell() {
+        let mut test_schedule = Schedule::new();
+        test_schedule
+            .add_cell(TEST_SLOTFRAME, TEST_CELL, TEST_NEIGHBOR, TEST_CELL_OPTIONS)
+            .unwrap();
+
+        // RUN TEST
+        let result = test_schedule.remove_cell(TEST_SLOTFRAME, &TEST_CELL);
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result.unwrap().neighbor, TEST_NEIGHBOR);
+        assert!(!test_schedule.is_scheduled(TEST_SLOTFRAME, &TEST_CELL));
+    }
+
+    #[test]
+    fn test_remove_neighbor() {
+        let mut test_schedule = Schedule::new();
+        let other_cell = Cell {
+            slot_offset: 5,
+            channel_offset: 2,
+        };
+        test_schedule
+            .add_cell(TEST_SLOTFRAME, TEST_CELL, TEST_NEIGHBOR, TEST_CELL_OPTIONS)
+            .unwrap();
+        test_schedule
+            .add_cell(
+                TEST_SLOTFRAME,
+                other_cell,
+                TEST_OTHER_NEIGHBOR,
+                TEST_CELL_OPTIONS,
+            )
+            .unwrap();
+
+        // RUN TEST
+        let result = test_schedule.remove_neighbor(TEST_NEIGHBOR);
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result, 1);
+        assert!(!test_schedule.is_scheduled(TEST_SLOTFRAME, &TEST_CELL));
+        assert!(test_schedule.is_scheduled(TEST_SLOTFRAME, &other_cell));
+    }
+
+    #[test]
+    fn test_count_cells() {
+        let mut test_schedule = Schedule::new();
+        test_schedule
+            .add_cell(TEST_SLOTFRAME, TEST_CELL, TEST_NEIGHBOR, TEST_CELL_OPTIONS)
+            .unwrap();
+        test_schedule
+            .add_cell(
+                TEST_SLOTFRAME,
+                Cell {
+                    slot_offset: 5,
+                    channel_offset: 2,
+                },
+                TEST_OTHER_NEIGHBOR,
+                TEST_CELL_OPTIONS,
+            )
+            .unwrap();
+
+        // RUN TEST
+        let result = test_schedule.count_cells(TEST_SLOTFRAME, TEST_NEIGHBOR, TEST_CELL_OPTIONS);
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn test_list_cells() {
+        let mut test_schedule = Schedule::new();
+        let other_cell = Cell {
+            slot_offset: 1,
+            channel_offset: 7,
+        };
+        test_schedule
+            .add_cell(TEST_SLOTFRAME, TEST_CELL, TEST_NEIGHBOR, TEST_CELL_OPTIONS)
+            .unwrap();
+        test_schedule
+            .add_cell(TEST_SLOTFRAME, other_cell, TEST_NEIGHBOR, TEST_CELL_OPTIONS)
+            .unwrap();
+
+        // RUN TEST
+        let result = test_schedule.list_cells(TEST_SLOTFRAME, TEST_NEIGHBOR, TEST_CELL_OPTIONS);
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result, [other_cell, TEST_CELL]);
+    }
+
+    #[test]
+    fn test_is_scheduled_with_wrong_options() {
+        let mut test_schedule = Schedule::new();
+        test_schedule
+            .add_cell(TEST_SLOTFRAME, TEST_CELL, TEST_NEIGHBOR, TEST_CELL_OPTIONS)
+            .unwrap();
+
+        // RUN TEST
+        let result = test_schedule.is_scheduled_with(
+            TEST_SLOTFRAME,
+            &TEST_CELL,
+            TEST_NEIGHBOR,
+            CellOptions::RX,
+        );
+
+        // ASSERT POSTCONDITION
+        assert!(!result);
+    }
+
+    #[test]
+    fn test_add_cell_other_slotframe() {
+        let mut test_schedule = Schedule::new();
+        test_schedule
+            .add_cell(TEST_SLOTFRAME, TEST_CELL, TEST_NEIGHBOR, TEST_CELL_OPTIONS)
+            .unwrap();
+
+        // RUN TEST
+        let result = test_schedule.add_cell(
+            TEST_SLOTFRAME + 1,
+            TEST_CELL,
+            TEST_OTHER_NEIGHBOR,
+            TEST_CELL_OPTIONS,
+        );
+
+        // ASSERT POSTCONDITION
+        assert!(result.is_ok());
+        assert_eq!(test_schedule.len(), 2);
+        assert_eq!(
+            test_schedule.count_cells(TEST_SLOTFRAME, TEST_NEIGHBOR, TEST_CELL_OPTIONS),
+            1
+        );
+    }
+
+    #[test]
+    fn test_lock_cells() {
+        let mut test_schedule = Schedule::new();
+        let other_cell = Cell {
+            slot_offset: 5,
+            channel_offset: 2,
+        };
+        test_schedule
+            .lock_cells(TEST_SLOTFRAME, TEST_NEIGHBOR, &[TEST_CELL])
+            .unwrap();
+
+        // RUN TEST
+        let result = test_schedule.lock_cells(
+            TEST_SLOTFRAME,
+            TEST_OTHER_NEIGHBOR,
+            &[other_cell, TEST_CELL],
+        );
+
+        // ASSERT POSTCONDITION
+        assert!(result.is_err());
+        assert_eq!(
+            test_schedule.locked_by(TEST_SLOTFRAME, &TEST_CELL),
+            Some(TEST_NEIGHBOR)
+        );
+        // nothing is locked if one of the cells is taken
+        assert!(!test_schedule.is_locked(TEST_SLOTFRAME, &other_cell));
+        assert!(!test_schedule.is_available(TEST_SLOTFRAME, &TEST_CELL));
+    }
+
+    #[test]
+    fn test_add_cell_locked() {
+        let mut test_schedule = Schedule::new();
+        test_schedule
+            .lock_cells(TEST_SLOTFRAME, TEST_NEIGHBOR, &[TEST_CELL])
+            .unwrap();
+
+        // RUN TEST
+        let other_result = test_schedule.add_cell(
+            TEST_SLOTFRAME,
+            TEST_CELL,
+            TEST_OTHER_NEIGHBOR,
+            TEST_CELL_OPTIONS,
+        );
+        let owner_result =
+            test_schedule.add_cell(TEST_SLOTFRAME, TEST_CELL, TEST_NEIGHBOR, TEST_CELL_OPTIONS);
+
+        // ASSERT POSTCONDITION
+        assert!(other_result.is_err());
+        assert!(owner_result.is_ok());
+    }
+
+    #[test]
+    fn test_unlock_cells() {
+        let mut test_schedule = Schedule::new();
+        test_schedule
+            .lock_cells(TEST_SLOTFRAME, TEST_NEIGHBOR, &[TEST_CELL])
+            .unwrap();
+
+        // RUN TEST
+        test_schedule.unlock_cells(TEST_NEIGHBOR);
+
+        // ASSERT POSTCONDITION
+        assert!(!test_schedule.is_locked(TEST_SLOTFRAME, &TEST_CELL));
+        assert!(test_schedule.is_available(TEST_SLOTFRAME, &TEST_CELL));
+    }
+
+    #[test]
+    fn test_add_cell_full() {
+        let mut test_schedule = Schedule::new();
+        for slot_offset in 0..MAX_SCHEDULED_CELLS {
+            let cell = Cell {
+                slot_offset: slot_offset as u16,
+                channel_offset: 0,
+            };
+            test_schedule
+                .add_cell(TEST_SLOTFRAME, cell, TEST_NEIGHBOR, TEST_CELL_OPTIONS)
+                .unwrap();
+        }
+
+        // RUN TEST
+        let result = test_schedule.add_cell(
+            TEST_SLOTFRAME + 1,
+            TEST_CELL,
+            TEST_NEIGHBOR,
+            TEST_CELL_OPTIONS,
+        );
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result, Err(SixtopError::CapacityExceeded));
+        assert_eq!(test_schedule.len(), MAX_SCHEDULED_CELLS);
+    }
+}
@@ -0,0 +1,246 @@
+/**
+ * The interface between 6P and the Scheduling Functions (SFs) that decide which cells to
+ * schedule, RFC 8480 Section 4.
+ *
+ * Sixtop calls an SF whenever something happens in a transaction that carries its SFID. The SF
+ * can't call back into Sixtop from there, so the callbacks get a SixtopHandle instead, through
+ * which the SF asks for transactions of its own. They are queued until the application starts
+ * them with Sixtop::poll_sf_request() and sends the request.
+ */
+use crate::error::SixtopError;
+use crate::types::{
+    Cell, CellList, CellOptions, NeighborID, Payload, Request, RequestType, ReturnCode,
+    MAX_SF_REQUESTS, SFID,
+};
+use crate::{
+    cell_request, count_request, list_request, new_request, relocate_request, signal_request,
+};
+
+/// Requests SFs asked for that haven't been started yet.
+pub(crate) type SfRequests = heapless::Deque<(NeighborID, Request), MAX_SF_REQUESTS>;
+
+/// How a transaction we initiated ended, see SchedulingFunction::handle_outcome().
+#[derive(Debug, PartialEq)]
+pub enum TransactionOutcome<'a> {
+    /// the neighbor answered with `code`. `cell_list` holds the cells that were added, deleted
+    /// or relocated to, or the cells of a LIST response. It is empty if the neighbor answered
+    /// with an error.
+    Answered {
+        code: ReturnCode,
+        cell_list: &'a [Cell],
+    },
+    /// the neighbor answered our COUNT request with `code`. `num_cells` is the number of cells
+    /// it has scheduled with us, or 0 if it answered with an error.
+    Counted { code: ReturnCode, num_cells: u16 },
+    /// the neighbor answered our SIGNAL request with `code` and `payload`, which only the SF can
+    /// interpret
+    Signaled { code: ReturnCode, payload: &'a [u8] },
+    /// the neighbor cleared its schedule with us before answering, or Sixtop::remove_neighbor()
+    /// was called, which aborted the transaction. All cells we had scheduled with it are gone.
+    Cleared,
+    /// the request couldn't be sent, e.g. because there already is a transaction with the
+    /// neighbor
+    NotStarted(SixtopError),
+    /// the MAC layer couldn't deliver the request or our confirmation, nothing changed
+    NotDelivered,
+}
+
+pub trait SchedulingFunction {
+    /// The SFID of the requests this SF handles and sends.
+    fn sfid(&self) -> SFID;
+
+    /// Called when `neighbor` proposed `candidates` in answer to our 3-step `request`, or sent
+    /// them as the CandidateCellList of its 2-step ADD or RELOCATE `request`. Returns the cells
+    /// to confirm or to answer with; 6P only offers cells we can use and ignores more than
+    /// NumCells.
+    fn pick_cells(
+        &mut self,
+        _neighbor: NeighborID,
+        request: &Request,
+        candidates: &[Cell],
+    ) -> CellList {
+        candidates
+            .iter()
+            .take(request.num_cells as usize)
+            .copied()
+            .collect()
+    }
+
+    /// Called with the payload of a SIGNAL request from `neighbor`. The returned payload is sent
+    /// back in the SIGNAL response.
+    fn handle_signal(&mut self, _neighbor: NeighborID, _payload: &[u8]) -> Payload {
+        Payload::new()
+    }
+
+    /// Called when `neighbor` starts a 3-step ADD or RELOCATE transaction with us. Returns the
+    /// candidate cells to offer in our response; cells that are already scheduled are skipped.
+    fn propose_cells(&mut self, _neighbor: NeighborID, _request: &Request) -> CellList {
+        CellList::new()
+    }
+
+    /// Called with every `request` from `neighbor` once we answered it with `code`.
+    fn handle_request(
+        &mut self,
+        _neighbor: NeighborID,
+        _request: &Request,
+        _code: ReturnCode,
+        _sixtop: &mut SixtopHandle,
+    ) {
+    }
+
+    /// Called when the transaction we started with `request` ends, unless it timed out or
+    /// `neighbor` answered with RC_ERR_SEQNUM, see handle_timeout() and handle_seqnum_error().
+    fn handle_outcome(
+        &mut self,
+        _neighbor: NeighborID,
+        _request: &Request,
+        _outcome: TransactionOutcome,
+        _sixtop: &mut SixtopHandle,
+    ) {
+    }
+
+    /// Called when the transaction with `neighbor` that was started by `request` is aborted
+    /// because it timed out.
+    fn handle_timeout(
+        &mut self,
+        _neighbor: NeighborID,
+        _request: &Request,
+        _sixtop: &mut SixtopHandle,
+    ) {
+    }
+
+    /// Called when we and `neighbor` disagree about the SeqNum, RFC 8480 Section 3.4.6.2.
+    /// The schedules may be out of sync, which a CLEAR fixes.
+    fn handle_seqnum_error(&mut self, _neighbor: NeighborID, _sixtop: &mut SixtopHandle) {}
+}
+
+/// How Sixtop holds on to its SFs: in a Box with std, as a `&'static mut` (e.g. from a
+/// `static_cell`) on targets without an allocator.
+#[cfg(feature = "std")]
+pub type DynSchedulingFunction = Box<dyn SchedulingFunction>;
+#[cfg(not(feature = "std"))]
+pub type DynSchedulingFunction = &'static mut dyn SchedulingFunction;
+
+/// Lets an SF start transactions from within its callbacks. The requests carry the SFID of
+/// the SF and go out once the application calls Sixtop::poll_sf_request().
+///
+/// All methods return Err if MAX_SF_REQUESTS requests are already waiting or a cell list or
+/// payload is too long.
+pub struct SixtopHandle<'a> {
+    sfid: SFID,
+    metadata: u16,
+    requests: &'a mut SfRequests,
+}
+
+impl<'a> SixtopHandle<'a> {
+    pub(crate) fn new(sfid: SFID, metadata: u16, requests: &'a mut SfRequests) -> SixtopHandle<'a> {
+        SixtopHandle {
+            sfid,
+            metadata,
+            requests,
+        }
+    }
+
+    fn push(&mut self, neighbor: NeighborID, request: Request) -> Result<(), SixtopError> {
+        self.requests
+            .push_back((neighbor, request))
+            .map_err(|_| SixtopError::CapacityExceeded)
+    }
+
+    fn new_request(&self, code: RequestType) -> Request {
+        new_request(code, self.sfid, self.metadata)
+    }
+
+    /// Ask `neighbor` to add `num_cells` cells, see Sixtop::add_cells().
+    pub fn add_cells(
+        &mut self,
+        neighbor: NeighborID,
+        cell_options: CellOptions,
+        num_cells: u8,
+        candidates: &[Cell],
+    ) -> Result<(), SixtopError> {
+        let request = cell_request(
+            self.new_request(RequestType::ADD),
+            cell_options,
+            num_cells,
+            candidates,
+        )?;
+        self.push(neighbor, request)
+    }
+
+    /// Ask `neighbor` to delete `num_cells` of the `candidates`, see Sixtop::delete_cells().
+    pub fn delete_cells(
+        &mut self,
+        neighbor: NeighborID,
+        cell_options: CellOptions,
+        num_cells: u8,
+        candidates: &[Cell],
+    ) -> Result<(), SixtopError> {
+        let request = cell_request(
+            self.new_request(RequestType::DELETE),
+            cell_options,
+            num_cells,
+            candidates,
+        )?;
+        self.push(neighbor, request)
+    }
+
+    /// Ask `neighbor` to move the cells in `relocation_cell_list` to some of the `candidates`,
+    /// see Sixtop::relocate_cells().
+    pub fn relocate_cells(
+        &mut self,
+        neighbor: NeighborID,
+        cell_options: CellOptions,
+        relocation_cell_list: &[Cell],
+        candidates: &[Cell],
+    ) -> Result<(), SixtopError> {
+        let request = relocate_request(
+            self.new_request(RequestType::RELOCATE),
+            cell_options,
+            relocation_cell_list,
+            candidates,
+        )?;
+        self.push(neighbor, request)
+    }
+
+    /// Ask `neighbor` how many cells with `cell_options` it has scheduled with us, see
+    /// Sixtop::count_cells().
+    pub fn count_cells(
+        &mut self,
+        neighbor: NeighborID,
+        cell_options: CellOptions,
+    ) -> Result<(), SixtopError> {
+        let request = count_request(self.new_request(RequestType::COUNT), cell_options);
+        self.push(neighbor, request)
+    }
+
+    /// Ask `neighbor` for a page of the cells with `cell_options` it has scheduled with us, see
+    /// Sixtop::list_cells().
+    pub fn list_cells(
+        &mut self,
+        neighbor: NeighborID,
+        cell_options: CellOptions,
+        offset: u16,
+        max_num_cells: u16,
+    ) -> Result<(), SixtopError> {
+        let request = list_request(
+            self.new_request(RequestType::LIST),
+            cell_options,
+            offset,
+            max_num_cells,
+        );
+        self.push(neighbor, request)
+    }
+
+    /// Pass `payload` to the SF of `neighbor`, see Sixtop::signal().
+    pub fn signal(&mut self, neighbor: NeighborID, payload: &[u8]) -> Result<(), SixtopError> {
+        let request = signal_request(self.new_request(RequestType::SIGNAl), payload)?;
+        self.push(neighbor, request)
+    }
+
+    /// Ask `neighbor` to drop all cells it has scheduled with us, see Sixtop::clear().
+    pub fn clear(&mut self, neighbor: NeighborID) -> Result<(), SixtopError> {
+        let request = self.new_request(RequestType::CLEAR);
+        self.push(neighbor, request)
+    }
+}
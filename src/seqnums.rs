@@ -1,26 +1,19 @@
 #![allow(dead_code)]
 
-use crate::types::NeighborID;
+use crate::error::SixtopError;
+use crate::types::{NeighborID, MAX_NEIGHBORS};
 /**
  * Each node remembers the last used SeqNum for each neighbor.
  * That is, a node stores as many SeqNum values as it has neighbors.
  */
-use std::collections::HashMap;
+use heapless::LinearMap;
 
 pub type SeqNum = u8;
 pub const START_SEQNUM: SeqNum = 0;
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct SeqNums {
-    values: HashMap<NeighborID, SeqNum>,
-}
-
-impl Default for SeqNums {
-    fn default() -> SeqNums {
-        SeqNums {
-            values: HashMap::new(),
-        }
-    }
+    values: LinearMap<NeighborID, SeqNum, MAX_NEIGHBORS>,
 }
 
 impl SeqNums {
@@ -30,16 +23,17 @@ impl SeqNums {
         }
     }
 
-    /// TODO do I ever need this?
     /// If a SeqNum entry for `neighbor` already exists, return it.
     /// If it doesn't, create a new entry and return its initial seqnum.
-    pub fn guaranteed_get_seqnum(&mut self, neighbor: NeighborID) -> SeqNum {
-        match (*self).values.get(&neighbor) {
+    ///
+    /// returns Err if there's no room for another neighbor
+    pub fn guaranteed_get_seqnum(&mut self, neighbor: NeighborID) -> Result<SeqNum, SixtopError> {
+        match self.values.get(&neighbor) {
             None => {
-                self.add_neighbor(neighbor, START_SEQNUM);
-                START_SEQNUM
+                self.add_neighbor(neighbor, START_SEQNUM)?;
+                Ok(START_SEQNUM)
             }
-            Some(seqnum) => *seqnum,
+            Some(seqnum) => Ok(*seqnum),
         }
     }
 
@@ -52,34 +46,47 @@ impl SeqNums {
     /// decide what to do next, as described in Section 3.4.6.2.
     ///
     /// returns Ok(<neighbor seqnum>) if `seqnum` is legitimate,
-    ///         Err on seqnum inconsistency
-    pub fn verify(&mut self, neighbor: NeighborID, seqnum: SeqNum) -> Result<SeqNum, ()> {
+    ///         Err on seqnum inconsistency or if there's no room for a new neighbor
+    pub fn verify(&mut self, neighbor: NeighborID, seqnum: SeqNum) -> Result<SeqNum, SixtopError> {
         match self.get_seqnum(neighbor) {
             Some(known_seqnum) => {
                 match (seqnum, *known_seqnum) {
                     (0, 0) => Ok(seqnum),
                     (new, old) if new == old => Ok(seqnum),
                     /* inconsistency detected */
-                    _ => Err(()),
+                    (received, expected) => Err(SixtopError::SeqNumMismatch { expected, received }),
                 }
             }
             None => {
-                self.add_neighbor(neighbor, seqnum);
+                self.add_neighbor(neighbor, seqnum)?;
                 Ok(seqnum)
             }
         }
     }
 
-    pub fn add_neighbor(&mut self, neighbor: NeighborID, seqnum: SeqNum) {
-        (*self).values.insert(neighbor, seqnum);
+    pub fn add_neighbor(
+        &mut self,
+        neighbor: NeighborID,
+        seqnum: SeqNum,
+    ) -> Result<(), SixtopError> {
+        self.values
+            .insert(neighbor, seqnum)
+            .map_err(|_| SixtopError::CapacityExceeded)?;
+        Ok(())
     }
 
     pub fn get_seqnum(&mut self, neighbor: NeighborID) -> Option<&SeqNum> {
-        (*self).values.get(&neighbor)
+        self.values.get(&neighbor)
+    }
+
+    /// Forget the SeqNum of `neighbor`, which makes room for another neighbor. If we hear from
+    /// `neighbor` again, it starts over like a new neighbor.
+    pub fn remove_neighbor(&mut self, neighbor: NeighborID) {
+        self.values.remove(&neighbor);
     }
 
     pub fn reset_seqnum(&mut self, neighbor: NeighborID) {
-        let curr_seqnum = (*self).values.get_mut(&neighbor);
+        let curr_seqnum = self.values.get_mut(&neighbor);
         if let Some(s) = curr_seqnum {
             *s = 0;
         }
@@ -89,7 +96,7 @@ impl SeqNums {
      * @return the new sequence number if a sequence number for @p neighbor exists
      */
     pub fn increment_seqnum(&mut self, neighbor: NeighborID) {
-        let curr_seqnum = (*self).values.get_mut(&neighbor);
+        let curr_seqnum = self.values.get_mut(&neighbor);
         if let Some(s) = curr_seqnum {
             match *s {
                 0xFF => {
@@ -109,7 +116,7 @@ impl SeqNums {
 mod tests {
     use super::*;
 
-    const TEST_NEIGHBOR: NeighborID = 22;
+    const TEST_NEIGHBOR: NeighborID = NeighborID::Short(22);
     const TEST_SEQNUM: SeqNum = 3;
 
     // TODO more constants less copypasta
@@ -119,7 +126,9 @@ mod tests {
         let mut test_seqnums = SeqNums::new();
 
         // RUN TEST
-        test_seqnums.add_neighbor(TEST_NEIGHBOR, TEST_SEQNUM);
+        test_seqnums
+            .add_neighbor(TEST_NEIGHBOR, TEST_SEQNUM)
+            .unwrap();
 
         // ASSERT POSTCONDITION
         assert_eq!(
@@ -131,7 +140,9 @@ mod tests {
     #[test]
     fn test_get_seqnum() {
         let mut test_seqnums = SeqNums::new();
-        test_seqnums.add_neighbor(TEST_NEIGHBOR, TEST_SEQNUM);
+        test_seqnums
+            .add_neighbor(TEST_NEIGHBOR, TEST_SEQNUM)
+            .unwrap();
 
         // RUN TEST
         let result = test_seqnums.get_seqnum(TEST_NEIGHBOR);
@@ -143,7 +154,9 @@ mod tests {
     #[test]
     fn test_reset_seqnum() {
         let mut test_seqnums = SeqNums::new();
-        test_seqnums.add_neighbor(TEST_NEIGHBOR, TEST_SEQNUM);
+        test_seqnums
+            .add_neighbor(TEST_NEIGHBOR, TEST_SEQNUM)
+            .unwrap();
 
         // RUN TEST
         test_seqnums.reset_seqnum(TEST_NEIGHBOR);
@@ -152,10 +165,30 @@ mod tests {
         let result = test_seqnums.get_seqnum(TEST_NEIGHBOR).unwrap();
         assert_eq!(*result, 0);
     }
+    #[test]
+    fn test_remove_neighbor() {
+        let mut test_seqnums = SeqNums::new();
+        for neighbor in 0..MAX_NEIGHBORS {
+            test_seqnums
+                .add_neighbor(NeighborID::Short(neighbor as u16), TEST_SEQNUM)
+                .unwrap();
+        }
+
+        // RUN TEST
+        test_seqnums.remove_neighbor(NeighborID::Short(0));
+
+        // ASSERT POSTCONDITION
+        assert_eq!(test_seqnums.get_seqnum(NeighborID::Short(0)), None);
+        let new_neighbor = NeighborID::Short(MAX_NEIGHBORS as u16);
+        assert_eq!(test_seqnums.verify(new_neighbor, 0), Ok(0));
+    }
+
     #[test]
     fn test_increment_seqnum() {
         let mut test_seqnums = SeqNums::new();
-        test_seqnums.add_neighbor(TEST_NEIGHBOR, TEST_SEQNUM);
+        test_seqnums
+            .add_neighbor(TEST_NEIGHBOR, TEST_SEQNUM)
+            .unwrap();
         let next_seqnum = TEST_SEQNUM + 1;
 
         // RUN TEST
@@ -174,7 +207,9 @@ mod tests {
     fn test_increment_seqnum_wraparound() {
         let mut test_seqnums = SeqNums::new();
         let max_seqnum = 0xFF;
-        test_seqnums.add_neighbor(TEST_NEIGHBOR, max_seqnum);
+        test_seqnums
+            .add_neighbor(TEST_NEIGHBOR, max_seqnum)
+            .unwrap();
 
         // RUN TEST
         test_seqnums.increment_seqnum(TEST_NEIGHBOR);
@@ -183,4 +218,45 @@ mod tests {
         let result = test_seqnums.get_seqnum(TEST_NEIGHBOR).unwrap();
         assert_eq!(*result, 1);
     }
+
+    #[test]
+    fn test_verify_mismatch() {
+        let mut test_seqnums = SeqNums::new();
+        test_seqnums
+            .add_neighbor(TEST_NEIGHBOR, TEST_SEQNUM)
+            .unwrap();
+
+        // RUN TEST
+        let result = test_seqnums.verify(TEST_NEIGHBOR, TEST_SEQNUM + 1);
+
+        // ASSERT POSTCONDITION
+        assert_eq!(
+            result,
+            Err(SixtopError::SeqNumMismatch {
+                expected: TEST_SEQNUM,
+                received: TEST_SEQNUM + 1,
+            })
+        );
+    }
+
+    #[test]
+    fn test_add_neighbor_full() {
+        let mut test_seqnums = SeqNums::new();
+        for neighbor in 0..MAX_NEIGHBORS {
+            test_seqnums
+                .add_neighbor(NeighborID::Short(neighbor as u16), 0)
+                .unwrap();
+        }
+
+        // RUN TEST
+        let result = test_seqnums.verify(NeighborID::Short(MAX_NEIGHBORS as u16), TEST_SEQNUM);
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result, Err(SixtopError::CapacityExceeded));
+        // neighbors we already know are still fine
+        assert_eq!(
+            test_seqnums.guaranteed_get_seqnum(NeighborID::Short(0)),
+            Ok(0)
+        );
+    }
 }
@@ -0,0 +1,209 @@
+#![allow(dead_code)]
+
+/**
+ * A 6P Transaction is a request and its response, optionally followed by a confirmation
+ * (3-step transaction). Whoever sent the request is the initiator of the transaction, the
+ * other node is the responder.
+ */
+use crate::types::{CellList, CellOptions, Request, RequestType, ReturnCode};
+
+/// Point in time in milliseconds. Where the time comes from is up to the user of the crate, it
+/// only has to be monotonic.
+pub type Timestamp = u64;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Role {
+    Initiator,
+    Responder,
+}
+
+/// State of the transaction with a neighbor. There can only be one transaction per neighbor
+/// at a time.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum TransactionState {
+    /// no ongoing transaction
+    Idle,
+    /// we sent a request and wait for the response
+    WaitingForResponse,
+    /// we sent the response to a 3-step request and wait for the confirmation
+    WaitingForConfirmation,
+    /// we sent the last message of the transaction and wait for the MAC layer to report
+    /// whether it was acknowledged
+    WaitingForLinkAck,
+}
+
+/// How long (in milliseconds) we wait in each state before aborting the transaction.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Timeouts {
+    pub response: u64,
+    pub confirmation: u64,
+    pub link_ack: u64,
+}
+
+impl Default for Timeouts {
+    fn default() -> Timeouts {
+        Timeouts {
+            response: 10_000,
+            confirmation: 10_000,
+            link_ack: 2_000,
+        }
+    }
+}
+
+impl Timeouts {
+    pub fn get(&self, state: TransactionState) -> u64 {
+        match state {
+            TransactionState::Idle => 0,
+            TransactionState::WaitingForResponse => self.response,
+            TransactionState::WaitingForConfirmation => self.confirmation,
+            TransactionState::WaitingForLinkAck => self.link_ack,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Transaction {
+    pub role: Role,
+    pub state: TransactionState,
+    /// when the transaction is aborted if it doesn't progress
+    pub deadline: Timestamp,
+    /// the request that started the transaction
+    pub request: Request,
+    /// cells we offered in our response, only used if we're the responder of a 3-step
+    /// transaction
+    pub candidate_cell_list: CellList,
+    /// cells to add, delete or relocate to once the last message of the transaction was
+    /// acknowledged by the MAC layer
+    pub cell_list: CellList,
+    /// whether we know that the neighbor received our request, only used if we're the initiator
+    pub request_acked: bool,
+}
+
+impl Transaction {
+    pub fn new(role: Role, request: Request) -> Transaction {
+        Transaction {
+            role,
+            state: TransactionState::Idle,
+            deadline: 0,
+            request,
+            candidate_cell_list: CellList::new(),
+            cell_list: CellList::new(),
+            request_acked: false,
+        }
+    }
+
+    /// Move to `state` and restart the timeout for it.
+    pub fn set_state(&mut self, state: TransactionState, now: Timestamp, timeouts: &Timeouts) {
+        self.state = state;
+        self.deadline = now.saturating_add(timeouts.get(state));
+    }
+
+    pub fn is_expired(&self, now: Timestamp) -> bool {
+        now >= self.deadline
+    }
+
+    /// The CellOptions of the request from our point of view, see CellOptions::mirrored().
+    pub fn cell_options(&self) -> CellOptions {
+        match self.role {
+            Role::Initiator => self.request.cell_options,
+            Role::Responder => self.request.cell_options.mirrored(),
+        }
+    }
+
+    /// RFC 8480, Section 3.3: in a 3-step transaction, the initiator doesn't send a
+    /// (Candidate)CellList. The responder proposes cells in its response instead and the
+    /// initiator tells it which ones it picked in the confirmation.
+    pub fn is_three_step(&self) -> bool {
+        is_three_step_request(&self.request)
+    }
+}
+
+/// Whether `request` asks for cells to be added, deleted or relocated.
+pub fn changes_schedule(request: &Request) -> bool {
+    matches!(
+        request.header.code,
+        RequestType::ADD | RequestType::DELETE | RequestType::RELOCATE
+    )
+}
+
+pub fn is_three_step_request(request: &Request) -> bool {
+    changes_schedule(request) && request.num_cells > 0 && request.cell_list.is_empty()
+}
+
+/// Whether a response with `code` means the responder processed the request. Requests it turns
+/// down without processing them don't end a transaction, so neither side increments the SeqNum.
+/// Both sides have to agree on this, and the initiator only has the code to go by.
+pub fn request_processed(code: ReturnCode) -> bool {
+    !matches!(
+        code,
+        ReturnCode::RC_ERR
+            | ReturnCode::RC_ERR_VERSION
+            | ReturnCode::RC_ERR_SFID
+            | ReturnCode::RC_ERR_BUSY
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Cell, Msg};
+
+    #[test]
+    fn test_is_three_step() {
+        let mut request = Request::new();
+        request.header.code = RequestType::ADD;
+        request.num_cells = 2;
+
+        // RUN TEST
+        let result = Transaction::new(Role::Initiator, request).is_three_step();
+
+        // ASSERT POSTCONDITION
+        assert!(result);
+    }
+
+    #[test]
+    fn test_is_three_step_with_cell_list() {
+        let mut request = Request::new();
+        request.header.code = RequestType::ADD;
+        request.num_cells = 1;
+        request
+            .cell_list
+            .push(Cell {
+                slot_offset: 1,
+                channel_offset: 2,
+            })
+            .unwrap();
+
+        // RUN TEST
+        let result = Transaction::new(Role::Initiator, request).is_three_step();
+
+        // ASSERT POSTCONDITION
+        assert!(!result);
+    }
+
+    #[test]
+    fn test_set_state() {
+        let mut transaction = Transaction::new(Role::Initiator, Request::new());
+        let timeouts = Timeouts::default();
+
+        // RUN TEST
+        transaction.set_state(TransactionState::WaitingForResponse, 100, &timeouts);
+
+        // ASSERT POSTCONDITION
+        assert_eq!(transaction.state, TransactionState::WaitingForResponse);
+        assert!(!transaction.is_expired(100 + timeouts.response - 1));
+        assert!(transaction.is_expired(100 + timeouts.response));
+    }
+
+    #[test]
+    fn test_is_three_step_count() {
+        let mut request = Request::new();
+        request.header.code = RequestType::COUNT;
+
+        // RUN TEST
+        let result = Transaction::new(Role::Initiator, request).is_three_step();
+
+        // ASSERT POSTCONDITION
+        assert!(!result);
+    }
+}
@@ -1,16 +1,27 @@
 #![allow(dead_code)]
 
+use core::convert::TryFrom;
+use core::fmt;
+
+pub use crate::address::MacAddress;
+pub use crate::cell_options::CellOptions;
+use crate::error::SixtopError;
+pub use crate::scheduling_function::{DynSchedulingFunction, SchedulingFunction};
+
 #[repr(u8)]
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum MsgType {
     REQUEST = 0,
     RESPONSE = 1,
-    CONFIRMATION = 2, // currently unimplemented
+    CONFIRMATION = 2,
     Unassigned = 3,
 }
 
-#[repr(u8)]
+/// The Code of a request, RFC 8480 Section 6.2.4. Codes we don't know are kept as Unassigned,
+/// so that we can still answer the request.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
 pub enum RequestType {
+    #[default]
     Reserved,
     ADD,
     DELETE,
@@ -19,12 +30,16 @@ pub enum RequestType {
     LIST,
     SIGNAl,
     CLEAR,
-    Unassigned,
+    Unassigned(u8),
 }
 
+/// The Code of a response or confirmation, RFC 8480 Section 6.2.5. Codes we don't know are kept
+/// as Unassigned.
 #[allow(non_camel_case_types)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
 pub enum ReturnCode {
-    RC_SUCCESS = 0,
+    #[default]
+    RC_SUCCESS,
     RC_EOL,
     RC_ERR,
     RC_RESET,
@@ -34,99 +49,188 @@ pub enum ReturnCode {
     RC_ERR_CELLLIST,
     RC_ERR_BUSY,
     RC_ERR_LOCKED,
-    Unassigned,
+    Unassigned(u8),
 }
 
 //                            +-+-+-+-+-+-+-+-+
 // mask to get/set the T in   |Version| T | R |
 //                            +-+-+-+-+-+-+-+-+
 pub const PREAMBLE_TYPE_MASK: u8 = 0b00001100;
+pub const PREAMBLE_VERSION_MASK: u8 = 0b11110000;
+pub const PREAMBLE_RESERVED_MASK: u8 = 0b00000011;
 
 pub const SIXTOP_VERSION: u8 = 0;
 
-pub type NeighborID = u8; // todo use actually useful type
-pub type CellList = Vec<Cell>;
+pub const SIXTOP_HDR_SZ_BYTES: usize = 4;
+
+pub type NeighborID = MacAddress;
+
+/// Most cells a CellList can hold. That's more than an 802.15.4 frame can carry: after the
+/// MAC header, the IE headers and the 6P header and body, a 127-byte frame with short addresses
+/// and no other IEs has room for 25 cells, and a RELOCATE request has to split them between its
+/// two lists. Messages that get here through other means than a single frame may hold more.
+pub const MAX_NUM_CELLS: usize = 32;
+/// Largest SIGNAL payload we can send or receive.
+pub const MAX_PAYLOAD_SZ_BYTES: usize = 64;
+
+/// Number of neighbors we can keep SeqNums and transactions for. Motes are short on RAM,
+/// border routers have many children.
+#[cfg(feature = "std")]
+pub const MAX_NEIGHBORS: usize = 64;
+#[cfg(not(feature = "std"))]
+pub const MAX_NEIGHBORS: usize = 8;
+
+pub type CellList = heapless::Vec<Cell, MAX_NUM_CELLS>;
+pub type Payload = heapless::Vec<u8, MAX_PAYLOAD_SZ_BYTES>;
 
 #[allow(non_camel_case_types)]
 pub type SFID = u8;
 
 pub const DEFAULT_SFID: SFID = 0; // todo check with std
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+/// Number of SFs that can run side by side on a node, each with its own SFID.
+pub const MAX_SCHEDULING_FUNCTIONS: usize = 4;
+/// Number of transactions SFs can ask for before the application starts them.
+pub const MAX_SF_REQUESTS: usize = 4;
+
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
 pub struct Cell {
     pub slot_offset: u16,
     pub channel_offset: u16,
 }
 
-#[derive(Debug, PartialEq)]
-pub struct MsgHdr {
+/// The 6P header. `code` is a RequestType in requests and a ReturnCode in responses and
+/// confirmations; a MsgHdr<u8> is a header whose type we haven't looked at yet.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct MsgHdr<C = u8> {
+    /// RFC 8480, Section 3.4.5: we only speak SIXTOP_VERSION and answer requests of any other
+    /// version with RC_ERR_VERSION.
+    pub version: u8,
+    /// the R bits of the preamble. They should be zero and are ignored on reception, but kept
+    /// here so that the application can see that a neighbor set them.
+    pub reserved: u8,
     pub msg_type: MsgType,
-    pub code: u8, // RequestType for requests, ReturnCode for responses
+    pub code: C,
     pub sfid: u8,
     pub seqnum: u8,
 }
 
-#[derive(Debug, PartialEq)]
+pub type RequestHdr = MsgHdr<RequestType>;
+pub type ResponseHdr = MsgHdr<ReturnCode>;
+
+#[derive(Debug, PartialEq, Clone)]
 // TODO impl debug for this and the data structures it uses for nicer visualization?
 pub struct Request {
-    pub header: MsgHdr,
+    pub header: RequestHdr,
     pub metadata: u16,
-    pub cell_options: u8,
+    pub cell_options: CellOptions,
     pub num_cells: u8,
+    /// CandidateCellList for ADD and RELOCATE requests, CellList of the cells to delete for
+    /// DELETE requests
     pub cell_list: CellList,
-    // TODO how do we best add a second celllist for RELOCATE requests?
-    // use an option type?
+    /// RelocationCellList, only present in RELOCATE requests. Goes on the wire before the
+    /// CandidateCellList in `cell_list`.
+    pub relocation_cell_list: Option<CellList>,
+    /// Offset and MaxNumCells, only present in LIST requests
+    pub offset: u16,
+    pub max_num_cells: u16,
+    /// opaque payload of SIGNAL requests, interpreted by the SF
+    pub payload: Payload,
 }
 
 #[derive(Debug, PartialEq)]
 pub struct Response {
-    pub header: MsgHdr,
+    pub header: ResponseHdr,
     pub cell_list: CellList,
 }
 
+/// Third message of a 3-step transaction: the initiator tells the responder which of the
+/// proposed cells it picked.
+#[derive(Debug, PartialEq)]
+pub struct Confirmation {
+    pub header: ResponseHdr,
+    pub cell_list: CellList,
+}
+
+/// Response to a COUNT request. Instead of a CellList, it carries the number of cells.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CountResponse {
+    pub header: ResponseHdr,
+    pub num_cells: u16,
+}
+
+/// Response to a SIGNAL request, carrying an opaque payload for the SF.
+#[derive(Debug, PartialEq)]
+pub struct SignalResponse {
+    pub header: ResponseHdr,
+    pub payload: Payload,
+}
+
 // Meta container for parsing returns
+// Boxing the Request would need an allocator, which motes don't have.
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
 pub enum SixtopMsg {
     RequestMsg(Request),
     ResponseMsg(Response),
-}
-
-pub trait SchedulingFunction {
-    fn get_sfid() -> SFID;
-    fn pick_cells(cell_list: Vec<Cell>, num_cells: u8) -> Result<Vec<Cell>, ()>;
-    // TODO: how do I let the SF trigger requests?
+    CountResponseMsg(CountResponse),
+    SignalResponseMsg(SignalResponse),
+    ConfirmationMsg(Confirmation),
 }
 
 pub trait Msg {
     fn new() -> Self;
 }
 
-impl MsgHdr {
-    //        +-+-+-+-+-+-+-+-+   where version = SIXTOP_VERSION = 0
-    // create |Version| T | R |         T = REQUEST
-    //        +-+-+-+-+-+-+-+-+         R = 0b00
-    pub fn serialize(&self) -> Result<Vec<u8>, ()> {
-        let mut bytes = Vec::new();
-        let preamble: u8 = PREAMBLE_TYPE_MASK & ((self.msg_type as u8) << 2);
+impl<C: Copy + Into<u8>> MsgHdr<C> {
+    //        +-+-+-+-+-+-+-+-+   where version = SIXTOP_VERSION = 0 unless set otherwise
+    // create |Version| T | R |         T = msg_type
+    //        +-+-+-+-+-+-+-+-+         R = 0b00 unless set otherwise
+    pub fn to_bytes(&self) -> [u8; SIXTOP_HDR_SZ_BYTES] {
+        let preamble: u8 = (PREAMBLE_VERSION_MASK & (self.version << 4))
+            | (PREAMBLE_TYPE_MASK & ((self.msg_type as u8) << 2))
+            | (PREAMBLE_RESERVED_MASK & self.reserved);
 
-        bytes.push(preamble);
-        bytes.push(self.code);
-        bytes.push(self.sfid);
-        bytes.push(self.seqnum);
+        [preamble, self.code.into(), self.sfid, self.seqnum]
+    }
 
-        Ok(bytes)
+    /// The header with its code as it goes on the wire.
+    pub fn to_raw(&self) -> MsgHdr {
+        self.with_code(self.code.into())
+    }
+
+    #[cfg(feature = "std")]
+    pub fn serialize(&self) -> Result<Vec<u8>, SixtopError> {
+        Ok(self.to_bytes().to_vec())
+    }
+}
+
+impl<C> MsgHdr<C> {
+    /// The same header with another code, e.g. to turn a MsgHdr<u8> into a RequestHdr.
+    pub fn with_code<D>(&self, code: D) -> MsgHdr<D> {
+        MsgHdr {
+            version: self.version,
+            reserved: self.reserved,
+            msg_type: self.msg_type,
+            code,
+            sfid: self.sfid,
+            seqnum: self.seqnum,
+        }
     }
 }
 
-// TODO add getters and setterr for req/resp header fields, esp. header.code to avoid type confusion
 impl Msg for Request {
     fn new() -> Request {
         Request {
             header: MsgHdr::new(MsgType::REQUEST),
             metadata: 0,
-            cell_options: 0,
+            cell_options: CellOptions::NONE,
             num_cells: 0,
             cell_list: CellList::new(),
+            relocation_cell_list: None,
+            offset: 0,
+            max_num_cells: 0,
+            payload: Payload::new(),
         }
     }
 }
@@ -140,24 +244,242 @@ impl Msg for Response {
     }
 }
 
+impl SixtopMsg {
+    /// The header of the message, with its code as it goes on the wire.
+    pub fn header(&self) -> MsgHdr {
+        match self {
+            SixtopMsg::RequestMsg(request) => request.header.to_raw(),
+            SixtopMsg::ResponseMsg(response) => response.header.to_raw(),
+            SixtopMsg::CountResponseMsg(response) => response.header.to_raw(),
+            SixtopMsg::SignalResponseMsg(response) => response.header.to_raw(),
+            SixtopMsg::ConfirmationMsg(confirmation) => confirmation.header.to_raw(),
+        }
+    }
+}
+
+impl Msg for Confirmation {
+    fn new() -> Confirmation {
+        Confirmation {
+            header: MsgHdr::new(MsgType::CONFIRMATION),
+            cell_list: CellList::new(),
+        }
+    }
+}
+
+impl Msg for CountResponse {
+    fn new() -> CountResponse {
+        CountResponse {
+            header: MsgHdr::new(MsgType::RESPONSE),
+            num_cells: 0,
+        }
+    }
+}
+
+impl Msg for SignalResponse {
+    fn new() -> SignalResponse {
+        SignalResponse {
+            header: MsgHdr::new(MsgType::RESPONSE),
+            payload: Payload::new(),
+        }
+    }
+}
+
 impl MsgType {
-    pub fn from_u8(value: u8) -> Result<MsgType, ()> {
+    pub fn from_u8(value: u8) -> Result<MsgType, SixtopError> {
         match value {
             0 => Ok(MsgType::REQUEST),
             1 => Ok(MsgType::RESPONSE),
             2 => Ok(MsgType::CONFIRMATION),
-            _ => Err(()),
+            _ => Err(SixtopError::UnknownMsgType(value)),
+        }
+    }
+}
+
+impl RequestType {
+    /// Like try_from(), but keeps codes we don't know as Unassigned.
+    pub fn from_wire(value: u8) -> RequestType {
+        RequestType::try_from(value).unwrap_or(RequestType::Unassigned(value))
+    }
+
+    /// The code as it goes on the wire. Unlike u8::from(), this works in constants.
+    pub const fn to_u8(self) -> u8 {
+        match self {
+            RequestType::Reserved => 0,
+            RequestType::ADD => 1,
+            RequestType::DELETE => 2,
+            RequestType::RELOCATE => 3,
+            RequestType::COUNT => 4,
+            RequestType::LIST => 5,
+            RequestType::SIGNAl => 6,
+            RequestType::CLEAR => 7,
+            RequestType::Unassigned(value) => value,
+        }
+    }
+}
+
+impl TryFrom<u8> for RequestType {
+    type Error = SixtopError;
+
+    fn try_from(value: u8) -> Result<RequestType, SixtopError> {
+        match value {
+            0 => Ok(RequestType::Reserved),
+            1 => Ok(RequestType::ADD),
+            2 => Ok(RequestType::DELETE),
+            3 => Ok(RequestType::RELOCATE),
+            4 => Ok(RequestType::COUNT),
+            5 => Ok(RequestType::LIST),
+            6 => Ok(RequestType::SIGNAl),
+            7 => Ok(RequestType::CLEAR),
+            _ => Err(SixtopError::UnknownRequestType(value)),
+        }
+    }
+}
+
+impl From<RequestType> for u8 {
+    fn from(request_type: RequestType) -> u8 {
+        request_type.to_u8()
+    }
+}
+
+impl fmt::Display for RequestType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RequestType::Reserved => write!(f, "Reserved"),
+            RequestType::ADD => write!(f, "ADD"),
+            RequestType::DELETE => write!(f, "DELETE"),
+            RequestType::RELOCATE => write!(f, "RELOCATE"),
+            RequestType::COUNT => write!(f, "COUNT"),
+            RequestType::LIST => write!(f, "LIST"),
+            RequestType::SIGNAl => write!(f, "SIGNAL"),
+            RequestType::CLEAR => write!(f, "CLEAR"),
+            RequestType::Unassigned(value) => write!(f, "Unassigned({})", value),
+        }
+    }
+}
+
+impl ReturnCode {
+    /// Like try_from(), but keeps codes we don't know as Unassigned.
+    pub fn from_wire(value: u8) -> ReturnCode {
+        ReturnCode::try_from(value).unwrap_or(ReturnCode::Unassigned(value))
+    }
+
+    /// The code as it goes on the wire. Unlike u8::from(), this works in constants.
+    pub const fn to_u8(self) -> u8 {
+        match self {
+            ReturnCode::RC_SUCCESS => 0,
+            ReturnCode::RC_EOL => 1,
+            ReturnCode::RC_ERR => 2,
+            ReturnCode::RC_RESET => 3,
+            ReturnCode::RC_ERR_VERSION => 4,
+            ReturnCode::RC_ERR_SFID => 5,
+            ReturnCode::RC_ERR_SEQNUM => 6,
+            ReturnCode::RC_ERR_CELLLIST => 7,
+            ReturnCode::RC_ERR_BUSY => 8,
+            ReturnCode::RC_ERR_LOCKED => 9,
+            ReturnCode::Unassigned(value) => value,
         }
     }
 }
 
-impl MsgHdr {
-    pub fn new(msg_type: MsgType) -> MsgHdr {
+impl TryFrom<u8> for ReturnCode {
+    type Error = SixtopError;
+
+    fn try_from(value: u8) -> Result<ReturnCode, SixtopError> {
+        match value {
+            0 => Ok(ReturnCode::RC_SUCCESS),
+            1 => Ok(ReturnCode::RC_EOL),
+            2 => Ok(ReturnCode::RC_ERR),
+            3 => Ok(ReturnCode::RC_RESET),
+            4 => Ok(ReturnCode::RC_ERR_VERSION),
+            5 => Ok(ReturnCode::RC_ERR_SFID),
+            6 => Ok(ReturnCode::RC_ERR_SEQNUM),
+            7 => Ok(ReturnCode::RC_ERR_CELLLIST),
+            8 => Ok(ReturnCode::RC_ERR_BUSY),
+            9 => Ok(ReturnCode::RC_ERR_LOCKED),
+            _ => Err(SixtopError::UnknownReturnCode(value)),
+        }
+    }
+}
+
+impl From<ReturnCode> for u8 {
+    fn from(return_code: ReturnCode) -> u8 {
+        return_code.to_u8()
+    }
+}
+
+impl fmt::Display for ReturnCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReturnCode::Unassigned(value) => write!(f, "Unassigned({})", value),
+            // the names are the ones RFC 8480 uses
+            _ => fmt::Debug::fmt(self, f),
+        }
+    }
+}
+
+impl<C: Default> MsgHdr<C> {
+    pub fn new(msg_type: MsgType) -> MsgHdr<C> {
         MsgHdr {
-            msg_type: msg_type,
-            code: 0,
+            version: SIXTOP_VERSION,
+            reserved: 0,
+            msg_type,
+            code: C::default(),
             sfid: DEFAULT_SFID,
             seqnum: 0,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::fmt::Write;
+
+    #[test]
+    fn test_request_type_try_from() {
+        // ASSERT POSTCONDITION
+        assert_eq!(RequestType::try_from(3), Ok(RequestType::RELOCATE));
+        assert_eq!(
+            RequestType::try_from(8),
+            Err(SixtopError::UnknownRequestType(8))
+        );
+        assert_eq!(RequestType::from_wire(8), RequestType::Unassigned(8));
+        assert_eq!(u8::from(RequestType::from_wire(8)), 8);
+        for code in 0..=7 {
+            assert_eq!(u8::from(RequestType::try_from(code).unwrap()), code);
+        }
+    }
+
+    #[test]
+    fn test_return_code_try_from() {
+        // ASSERT POSTCONDITION
+        assert_eq!(ReturnCode::try_from(6), Ok(ReturnCode::RC_ERR_SEQNUM));
+        assert_eq!(
+            ReturnCode::try_from(10),
+            Err(SixtopError::UnknownReturnCode(10))
+        );
+        assert_eq!(ReturnCode::from_wire(10), ReturnCode::Unassigned(10));
+        for code in 0..=9 {
+            assert_eq!(u8::from(ReturnCode::try_from(code).unwrap()), code);
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        let mut result = heapless::String::<64>::new();
+
+        // RUN TEST
+        write!(
+            result,
+            "{} {} {} {}",
+            RequestType::SIGNAl,
+            RequestType::Unassigned(8),
+            ReturnCode::RC_ERR_BUSY,
+            ReturnCode::Unassigned(10)
+        )
+        .unwrap();
+
+        // ASSERT POSTCONDITION
+        assert_eq!(result, "SIGNAL Unassigned(8) RC_ERR_BUSY Unassigned(10)");
+    }
+}